@@ -0,0 +1,425 @@
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::error::{Error, GraphQLError};
+use crate::model::{Collection, ListType, MediaListGroup, User, Viewer};
+use crate::ratelimit::RateLimiter;
+use crate::snapshot::Snapshot;
+
+pub const ANILIST_ENDPOINT: &str = "https://graphql.anilist.co/";
+
+// AniList caps how many entries a single MediaListCollection returns, so lists are fetched in
+// chunks of up to 500 entries until hasNextChunk is false
+const PER_CHUNK: u32 = 500;
+
+// a full chunk takes AniList a few seconds at worst, so a query taking this long has stalled
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const LIST_QUERY: &str = "
+query ($userName: String, $type: MediaType, $chunk: Int, $perChunk: Int) {
+  MediaListCollection(userName: $userName, type: $type, chunk: $chunk, perChunk: $perChunk) {
+    user {
+      id
+    }
+    hasNextChunk
+    lists {
+      entries {
+        id
+        status
+        repeat
+        progress
+        progressVolumes
+        customLists
+        hiddenFromStatusLists
+        startedAt {
+          year
+          month
+          day
+        }
+        completedAt {
+          year
+          month
+          day
+        }
+        createdAt
+        updatedAt
+        score
+        notes
+        media {
+          id
+          idMal
+          isAdult
+          title {
+            romaji
+          }
+          format
+          episodes
+          chapters
+          volumes
+        }
+        priority
+      }
+      isCustomList
+    }
+  }
+}
+";
+
+const ANISTATS_QUERY: &str = "
+query ($name: String) {
+  User(name: $name) {
+    id
+    name
+    statistics {
+      anime {
+        count
+        statuses {
+          status
+          count
+        }
+      }
+    }
+  }
+}
+";
+
+const MANGASTATS_QUERY: &str = "
+query ($name: String) {
+  User(name: $name) {
+    id
+    name
+    statistics {
+      manga {
+        count
+        statuses {
+          status
+          count
+        }
+      }
+    }
+  }
+}";
+
+const VIEWER_QUERY: &str = "
+query {
+  Viewer {
+    id
+    name
+  }
+}";
+
+/// A query about to be retried, as reported to the callback given to
+/// [`AniListClient::on_retry`].
+#[derive(Clone, Debug)]
+pub struct Retry {
+    pub reason: RetryReason,
+    pub delay: Duration,
+    /// which retry this is, counting from 1
+    pub attempt: u32,
+    pub max_retries: u32,
+}
+
+#[derive(Clone, Debug)]
+pub enum RetryReason {
+    /// AniList answered with a rate limit or server error
+    Status(StatusCode),
+    TimedOut,
+    ConnectFailed,
+}
+
+impl fmt::Display for Retry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            RetryReason::Status(status) => {
+                write!(f, "AniList answered with HTTP {}", status.as_u16())?
+            }
+            RetryReason::TimedOut => f.write_str("Query failed (timed out)")?,
+            RetryReason::ConnectFailed => f.write_str("Query failed (couldn't connect)")?,
+        }
+        write!(
+            f,
+            ", retrying in {}s ({}/{})",
+            self.delay.as_secs(),
+            self.attempt,
+            self.max_retries
+        )
+    }
+}
+
+type RetryCallback = Box<dyn Fn(&Retry) + Send + Sync>;
+
+/// Client for the AniList GraphQL API.
+///
+/// Every query made through the same client shares one rate limiter, so a single client should be
+/// reused for all the queries of a run.
+pub struct AniListClient {
+    client: reqwest::Client,
+    endpoint: String,
+    limiter: RateLimiter,
+    on_retry: Option<RetryCallback>,
+}
+
+impl AniListClient {
+    /// `auth_pin` is the full Authorization header value (`Bearer <token>`), or empty to make
+    /// anonymous queries.
+    pub fn new(endpoint: &str, auth_pin: &str, max_retries: u32) -> Result<AniListClient, Error> {
+        let mut headers = HeaderMap::new();
+        if !auth_pin.is_empty() {
+            let mut auth_header =
+                HeaderValue::from_str(auth_pin).map_err(|_| Error::InvalidToken)?;
+            // keeps the token out of debug output
+            auth_header.set_sensitive(true);
+            headers.insert(AUTHORIZATION, auth_header);
+        }
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+
+        Ok(AniListClient {
+            client: Client::builder()
+                .default_headers(headers)
+                .timeout(REQUEST_TIMEOUT)
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()?,
+            endpoint: endpoint.to_string(),
+            limiter: RateLimiter::new(max_retries),
+            on_retry: None,
+        })
+    }
+
+    /// Calls `on_retry` before every retry, for showing why the export is taking longer.
+    pub fn on_retry(mut self, on_retry: impl Fn(&Retry) + Send + Sync + 'static) -> AniListClient {
+        self.on_retry = Some(Box::new(on_retry));
+        self
+    }
+
+    fn retrying(&self, reason: RetryReason, delay: Duration, attempt: u32) {
+        if let Some(on_retry) = &self.on_retry {
+            on_retry(&Retry {
+                reason,
+                delay,
+                attempt: attempt + 1,
+                max_retries: self.limiter.max_retries,
+            });
+        }
+    }
+
+    /// Sends a query and returns its `data` field, retrying as long as the rate limiter allows.
+    pub async fn query(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<serde_json::Value, Error> {
+        let query_body = json!({
+            "query" : query,
+            "variables" : variables
+        })
+        .to_string();
+
+        let mut attempt = 0;
+        let (status, body) = loop {
+            self.limiter.wait().await;
+            let sent = self
+                .client
+                .post(&self.endpoint)
+                .body(query_body.clone())
+                .send()
+                .await;
+            let resp = match sent {
+                Ok(resp) => resp,
+                // a dropped connection or a stalled query is as likely to be temporary
+                Err(e)
+                    if (e.is_timeout() || e.is_connect()) && attempt < self.limiter.max_retries =>
+                {
+                    let delay = self.limiter.backoff(attempt);
+                    let reason = if e.is_timeout() {
+                        RetryReason::TimedOut
+                    } else {
+                        RetryReason::ConnectFailed
+                    };
+                    self.retrying(reason, delay, attempt);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            self.limiter.observe(resp.headers());
+
+            let status = resp.status();
+            if self.limiter.should_retry(status, attempt) {
+                let delay = self.limiter.retry_delay(resp.headers(), attempt);
+                self.retrying(RetryReason::Status(status), delay, attempt);
+                attempt += 1;
+                continue;
+            }
+            break (status, resp.text().await?);
+        };
+
+        // AniList sends a GraphQL error body along with most non-2xx statuses, and it is the more
+        // descriptive of the two
+        let mut response: serde_json::Value = match serde_json::from_str(&body) {
+            Ok(response) => response,
+            Err(_) if !status.is_success() => return Err(Error::Http { status, body }),
+            Err(e) => {
+                return Err(Error::Parse {
+                    what: "the response body",
+                    source: e,
+                })
+            }
+        };
+        if let Some(errors) = response.get("errors").filter(|e| !e.is_null()) {
+            let errors: Vec<GraphQLError> =
+                serde_json::from_value(errors.clone()).map_err(|e| Error::Parse {
+                    what: "the errors array",
+                    source: e,
+                })?;
+            return Err(Error::GraphQL(errors));
+        }
+        if !status.is_success() {
+            return Err(Error::Http { status, body });
+        }
+        match response.get_mut("data") {
+            Some(data) if !data.is_null() => Ok(data.take()),
+            _ => Err(Error::MissingData("data")),
+        }
+    }
+
+    /// Fetches the owner of the client's token, which fails if AniList doesn't accept the token.
+    pub async fn viewer(&self) -> Result<Viewer, Error> {
+        let result = self.query(VIEWER_QUERY, json!({})).await?;
+        parse_field(&result["Viewer"], "the token's owner")
+    }
+
+    /// Fetches the user along with the statistics of their list of the given type.
+    pub async fn user(&self, username: &str, list_type: ListType) -> Result<User, Error> {
+        let result = self.user_response(username, list_type).await?;
+        parse_user(&result, list_type)
+    }
+
+    /// Fetches every list of the given type, status lists and custom lists alike.
+    pub async fn lists(
+        &self,
+        username: &str,
+        list_type: ListType,
+    ) -> Result<Vec<MediaListGroup>, Error> {
+        let mut lists: Vec<MediaListGroup> = Vec::new();
+        for result in self.list_responses(username, list_type).await? {
+            lists.extend(parse_lists(&result)?);
+        }
+        Ok(lists)
+    }
+
+    /// Fetches everything needed to export the user's list of the given type.
+    pub async fn collection(
+        &self,
+        username: &str,
+        list_type: ListType,
+    ) -> Result<Collection, Error> {
+        self.snapshot(username, list_type).await?.collection()
+    }
+
+    /// Like `collection`, but keeps the responses as AniList sent them so they can be saved and
+    /// exported again later without querying the API.
+    pub async fn snapshot(&self, username: &str, list_type: ListType) -> Result<Snapshot, Error> {
+        let user = self.user_response(username, list_type).await?;
+        // bail out on a bad user before making the list queries
+        parse_user(&user, list_type)?;
+
+        let mut responses = vec![user];
+        responses.extend(self.list_responses(username, list_type).await?);
+        Ok(Snapshot {
+            list_type,
+            responses,
+        })
+    }
+
+    async fn user_response(
+        &self,
+        username: &str,
+        list_type: ListType,
+    ) -> Result<serde_json::Value, Error> {
+        self.query(
+            match list_type {
+                ListType::Anime => ANISTATS_QUERY,
+                ListType::Manga => MANGASTATS_QUERY,
+            },
+            json!({ "name" : username }),
+        )
+        .await
+    }
+
+    async fn list_responses(
+        &self,
+        username: &str,
+        list_type: ListType,
+    ) -> Result<Vec<serde_json::Value>, Error> {
+        let mut responses = Vec::new();
+        let mut chunk = 1;
+        loop {
+            let result = self
+                .query(
+                    LIST_QUERY,
+                    json!({
+                        "userName" : username,
+                        "type" : list_type.media_type(),
+                        "chunk" : chunk,
+                        "perChunk" : PER_CHUNK
+                    }),
+                )
+                .await?;
+            let has_next_chunk = result["MediaListCollection"]["hasNextChunk"]
+                .as_bool()
+                .unwrap_or(false);
+            responses.push(result);
+            if !has_next_chunk {
+                break;
+            }
+            chunk += 1;
+        }
+
+        Ok(responses)
+    }
+}
+
+/// Reads the user out of the `data` of a User query.
+pub(crate) fn parse_user(result: &serde_json::Value, list_type: ListType) -> Result<User, Error> {
+    let statistics = match list_type {
+        ListType::Anime => parse_field(
+            &result["User"]["statistics"]["anime"],
+            "the user's anime statistics",
+        )?,
+        ListType::Manga => parse_field(
+            &result["User"]["statistics"]["manga"],
+            "the user's manga statistics",
+        )?,
+    };
+    Ok(User {
+        id: parse_field(&result["User"]["id"], "the user's id")?,
+        name: parse_field(&result["User"]["name"], "the user's name")?,
+        statistics,
+    })
+}
+
+/// Reads the lists out of the `data` of one MediaListCollection chunk.
+///
+/// A list spanning several chunks shows up once per chunk, which is fine since the entries of all
+/// lists are flattened before exporting.
+pub(crate) fn parse_lists(result: &serde_json::Value) -> Result<Vec<MediaListGroup>, Error> {
+    parse_field(&result["MediaListCollection"]["lists"], "the user's lists")
+}
+
+fn parse_field<T: DeserializeOwned>(
+    value: &serde_json::Value,
+    what: &'static str,
+) -> Result<T, Error> {
+    if value.is_null() {
+        return Err(Error::MissingData(what));
+    }
+    serde_json::from_value(value.clone()).map_err(|e| Error::Parse { what, source: e })
+}
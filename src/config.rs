@@ -0,0 +1,78 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::Error;
+use crate::model::ListSelection;
+use crate::writer::OutputFormat;
+
+/// Settings read from `config.toml`, overridden by environment variables and flags.
+///
+/// The top level keys are defaults for the export flags of the same name.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub user: Option<String>,
+    pub list: Option<ListSelection>,
+    pub file: Option<PathBuf>,
+    pub format: Option<OutputFormat>,
+    pub gzip: Option<bool>,
+    pub cdata: Option<bool>,
+    pub update: Option<bool>,
+    pub nsfw: Option<bool>,
+    pub endpoint: Option<String>,
+    pub token_file: Option<PathBuf>,
+    #[serde(default)]
+    pub oauth: OAuthConfig,
+}
+
+/// The AniList API client to log in through
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OAuthConfig {
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub token_endpoint: Option<String>,
+}
+
+impl Config {
+    /// Reads the config file at `path`, a missing file is the same as an empty one.
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        let input = match fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => {
+                return Err(Error::Config(format!(
+                    "couldn't read {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        toml::from_str(&input)
+            .map_err(|e| Error::Config(format!("{} isn't valid: {}", path.display(), e)))
+    }
+}
+
+/// The directory holding this program's configuration, following the XDG base directory spec
+/// (`$XDG_CONFIG_HOME`, falling back to `~/.config`) and `%APPDATA%` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(windows) {
+                std::env::var_os("APPDATA").map(PathBuf::from)
+            } else {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+            }
+        })?;
+    Some(base.join("mal-export-for-anilist"))
+}
+
+/// Where the config file is looked for.
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
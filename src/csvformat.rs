@@ -0,0 +1,95 @@
+use std::io::{self, Write};
+
+use crate::model::{Collection, Date, MediaEntry, Status};
+use crate::writer::OutputWriter;
+use crate::xmlformat::lists_to_tags;
+
+const COLUMNS: [&str; 13] = [
+    "mal_id",
+    "anilist_id",
+    "title",
+    "format",
+    "status",
+    "progress",
+    "progress_volumes",
+    "score",
+    "started_at",
+    "completed_at",
+    "repeat",
+    "notes",
+    "custom_lists",
+];
+
+/// Writes one CSV row per entry, keeping AniList's own values instead of mapping them to MAL's
+pub struct CsvWriter;
+
+impl OutputWriter for CsvWriter {
+    fn header(&mut self, f: &mut dyn Write, _collection: &Collection) -> io::Result<()> {
+        writeln!(f, "{}", COLUMNS.join(","))
+    }
+
+    fn entry(&mut self, f: &mut dyn Write, entry: &MediaEntry) -> io::Result<()> {
+        let row = [
+            entry
+                .media
+                .idMal
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            entry.media.id.to_string(),
+            entry.media.title.romaji.clone(),
+            entry
+                .media
+                .format
+                .as_ref()
+                .map(|format| format.to_string())
+                .unwrap_or_default(),
+            status_name(entry.status).to_string(),
+            entry.progress.to_string(),
+            entry
+                .progressVolumes
+                .map(|volumes| volumes.to_string())
+                .unwrap_or_default(),
+            entry.score.to_string(),
+            csv_date(&entry.startedAt),
+            csv_date(&entry.completedAt),
+            entry.repeat.to_string(),
+            entry.notes.clone().unwrap_or_default(),
+            lists_to_tags(&entry.customLists),
+        ];
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        writeln!(f, "{}", row.join(","))
+    }
+
+    fn footer(&mut self, _f: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::CURRENT => "CURRENT",
+        Status::PLANNING => "PLANNING",
+        Status::COMPLETED => "COMPLETED",
+        Status::DROPPED => "DROPPED",
+        Status::PAUSED => "PAUSED",
+        Status::REPEATING => "REPEATING",
+    }
+}
+
+// unknown dates are left empty rather than 0000-00-00 so spreadsheets don't misparse them
+fn csv_date(date: &Date) -> String {
+    if date.year.is_none() && date.month.is_none() && date.day.is_none() {
+        String::new()
+    } else {
+        date.to_string()
+    }
+}
+
+fn csv_field(input: &str) -> String {
+    // quoting as per RFC 4180
+    if input.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", input.replace('"', "\"\""))
+    } else {
+        input.to_string()
+    }
+}
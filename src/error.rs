@@ -0,0 +1,189 @@
+use std::fmt;
+use std::io;
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GraphQLError {
+    pub message: String,
+    pub status: Option<u16>,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum GraphQLErrorKind {
+    UserNotFound,
+    PrivateList,
+    InvalidToken,
+    RateLimited,
+    Other,
+}
+
+impl GraphQLError {
+    pub fn kind(&self) -> GraphQLErrorKind {
+        // AniList reports private profiles as 404s, only the message tells them apart
+        let message = self.message.to_lowercase();
+        match self.status {
+            Some(404) if message.contains("private") => GraphQLErrorKind::PrivateList,
+            Some(404) => GraphQLErrorKind::UserNotFound,
+            Some(401) => GraphQLErrorKind::InvalidToken,
+            Some(400) if message.contains("invalid token") => GraphQLErrorKind::InvalidToken,
+            Some(429) => GraphQLErrorKind::RateLimited,
+            _ if message.contains("private") => GraphQLErrorKind::PrivateList,
+            _ => GraphQLErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for GraphQLErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            GraphQLErrorKind::UserNotFound => {
+                "the user couldn't be found on AniList, check the spelling of the username"
+            }
+            GraphQLErrorKind::PrivateList => {
+                "the user's profile or list is private, use --oauth while logged in as them to export it"
+            }
+            GraphQLErrorKind::InvalidToken => {
+                "AniList rejected the OAuth token, it may have expired or been pasted incompletely"
+            }
+            GraphQLErrorKind::RateLimited => {
+                "the AniList API rate limit was hit, wait a minute before trying again"
+            }
+            GraphQLErrorKind::Other => "the AniList API rejected the query",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// the request never got an answer (DNS, TLS, connection reset, ...)
+    Transport(reqwest::Error),
+    /// non-2xx answer without a GraphQL error body
+    Http {
+        status: StatusCode,
+        body: String,
+    },
+    /// AniList answered with an `errors` array
+    GraphQL(Vec<GraphQLError>),
+    /// the answer is missing a field the export depends on
+    MissingData(&'static str),
+    /// a field is present, but doesn't have the expected shape
+    Parse {
+        what: &'static str,
+        source: serde_json::Error,
+    },
+    InvalidToken,
+    /// the OAuth login didn't produce a token, or a saved one can't be read
+    OAuth(String),
+    /// the config file can't be loaded
+    Config(String),
+    /// `validate` found problems in an export, they have been reported already
+    Invalid(usize),
+    /// the file given to --input, --roster, validate or diff can't be loaded
+    Input(String),
+    Io(io::Error),
+    /// some users of a roster couldn't be exported, their errors have been reported already
+    Batch {
+        failed: usize,
+        total: usize,
+    },
+}
+
+impl Error {
+    /// Exit code reported to the shell, 1 is reserved for panics and 2 for usage errors
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Io(_) => 3,
+            Error::Transport(_) => 4,
+            Error::Http { .. } => 5,
+            Error::GraphQL(_) => 6,
+            Error::InvalidToken => 7,
+            Error::MissingData(_) => 8,
+            Error::Parse { .. } => 9,
+            Error::Input(_) => 10,
+            Error::Batch { .. } => 11,
+            Error::OAuth(_) => 12,
+            Error::Config(_) => 13,
+            Error::Invalid(_) => 14,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Transport(e) => write!(f, "couldn't reach the AniList API: {}", e),
+            Error::Http { status, body } => {
+                write!(f, "the AniList API answered with HTTP {}", status)?;
+                if !body.trim().is_empty() {
+                    write!(f, ": {}", body.trim())?;
+                }
+                Ok(())
+            }
+            Error::GraphQL(errors) => {
+                let kind = errors
+                    .iter()
+                    .map(GraphQLError::kind)
+                    .find(|kind| *kind != GraphQLErrorKind::Other)
+                    .unwrap_or(GraphQLErrorKind::Other);
+                write!(f, "{}", kind)?;
+                for error in errors {
+                    match error.status {
+                        Some(status) => {
+                            write!(f, "\n  AniList says: {} (status {})", error.message, status)?
+                        }
+                        None => write!(f, "\n  AniList says: {}", error.message)?,
+                    }
+                }
+                Ok(())
+            }
+            Error::MissingData(what) => {
+                write!(f, "the AniList API response is missing {}", what)
+            }
+            Error::Parse { what, source } => {
+                write!(
+                    f,
+                    "couldn't parse {} from the API response: {}",
+                    what, source
+                )
+            }
+            Error::InvalidToken => write!(
+                f,
+                "the OAuth token contains characters that can't be sent in a header"
+            ),
+            Error::OAuth(reason) => write!(f, "couldn't log in to AniList: {}", reason),
+            Error::Invalid(1) => write!(f, "the export has a problem"),
+            Error::Invalid(problems) => write!(f, "the export has {} problems", problems),
+            Error::Config(reason) => write!(f, "couldn't load the config file: {}", reason),
+            Error::Input(reason) => write!(f, "couldn't load the input file: {}", reason),
+            Error::Io(e) => write!(f, "couldn't write the output: {}", e),
+            Error::Batch { failed, total } => {
+                write!(f, "{} of {} users couldn't be exported", failed, total)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            Error::Parse { source, .. } => Some(source),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Transport(e)
+    }
+}
@@ -0,0 +1,137 @@
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::client::AniListClient;
+use crate::csvformat::CsvWriter;
+use crate::error::Error;
+use crate::jsonformat::JsonWriter;
+use crate::model::{Collection, ListType, MediaEntry};
+use crate::writer::{ModifiedEntry, OutputFormat, OutputWriter};
+use crate::xmlformat::XmlWriter;
+
+/// Turns a user's list into an export, MAL style XML unless another format is chosen.
+#[derive(Copy, Clone, Debug)]
+pub struct Exporter {
+    pub list_type: ListType,
+    pub format: OutputFormat,
+    /// sets update_on_import, so importing overwrites entries already on the target list
+    pub update: bool,
+    /// include adult entries
+    pub nsfw: bool,
+    /// wrap the free text fields of XML exports in CDATA sections, like MAL's own exports
+    pub cdata: bool,
+}
+
+impl Exporter {
+    pub fn new(list_type: ListType) -> Exporter {
+        Exporter {
+            list_type,
+            format: OutputFormat::default(),
+            update: true,
+            nsfw: true,
+            cdata: false,
+        }
+    }
+
+    /// The backend for the chosen format.
+    pub fn writer(&self) -> Box<dyn OutputWriter> {
+        match self.format {
+            OutputFormat::Xml => {
+                Box::new(XmlWriter::new(self.list_type, self.update).with_cdata(self.cdata))
+            }
+            OutputFormat::Csv => Box::new(CsvWriter),
+            OutputFormat::Json => Box::new(JsonWriter::new()),
+        }
+    }
+
+    /// The entries that make it into the export.
+    ///
+    /// Entries on custom lists are also on a status list unless they are hidden from status
+    /// lists, so only those hidden ones are taken from custom lists to avoid duplicates.
+    pub fn entries<'a>(&self, collection: &'a Collection) -> impl Iterator<Item = &'a MediaEntry> {
+        let nsfw = self.nsfw;
+        let status_entries = collection
+            .lists
+            .iter()
+            .filter(|list| !list.isCustomList)
+            .flat_map(|list| &list.entries);
+        let hidden_entries = collection
+            .lists
+            .iter()
+            .filter(|list| list.isCustomList)
+            .flat_map(|list| &list.entries)
+            .filter(|entry| entry.hiddenFromStatusLists);
+        status_entries
+            .chain(hidden_entries)
+            .filter(move |entry| nsfw || !entry.media.isAdult)
+    }
+
+    /// Writes the export to `f` and returns the entries whose text had to be changed to fit it.
+    pub fn write<W: Write>(
+        &self,
+        collection: &Collection,
+        f: &mut W,
+    ) -> io::Result<Vec<ModifiedEntry>> {
+        self.write_with(self.writer().as_mut(), collection, f)
+    }
+
+    /// Like `write`, but with a backend of the caller's choosing instead of `format`.
+    pub fn write_with<W: Write>(
+        &self,
+        writer: &mut dyn OutputWriter,
+        collection: &Collection,
+        f: &mut W,
+    ) -> io::Result<Vec<ModifiedEntry>> {
+        writer.header(f, collection)?;
+        for media_entry in self.entries(collection) {
+            writer.entry(f, media_entry)?;
+        }
+        writer.footer(f)?;
+
+        f.flush()?;
+        Ok(writer.modified().to_vec())
+    }
+
+    /// Like `write`, but gzip compressed. MAL's import page takes `.xml.gz` files as they are.
+    pub fn write_gzip<W: Write>(
+        &self,
+        collection: &Collection,
+        f: &mut W,
+    ) -> io::Result<Vec<ModifiedEntry>> {
+        let mut encoder = GzEncoder::new(f, Compression::default());
+        let modified = self.write(collection, &mut encoder)?;
+        encoder.finish()?.flush()?;
+        Ok(modified)
+    }
+
+    pub fn render(&self, collection: &Collection) -> String {
+        let mut buffer: Vec<u8> = Vec::new();
+        self.write(collection, &mut buffer)
+            .expect("writing to a Vec can't fail");
+        String::from_utf8(buffer).expect("the export is built from UTF-8 strings")
+    }
+
+    /// Fetches the user's list and writes the export to `f`.
+    pub async fn export<W: Write>(
+        &self,
+        client: &AniListClient,
+        username: &str,
+        f: &mut W,
+    ) -> Result<(), Error> {
+        let collection = client.collection(username, self.list_type).await?;
+        self.write(&collection, f)?;
+        Ok(())
+    }
+
+    /// Fetches the user's list and returns the export as a string.
+    pub async fn export_string(
+        &self,
+        client: &AniListClient,
+        username: &str,
+    ) -> Result<String, Error> {
+        let collection = client.collection(username, self.list_type).await?;
+        Ok(self.render(&collection))
+    }
+}
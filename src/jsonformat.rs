@@ -0,0 +1,51 @@
+use std::io::{self, Write};
+
+use crate::model::{Collection, MediaEntry};
+use crate::writer::OutputWriter;
+
+/// Writes the entries exactly as AniList returned them, along with the user and their statistics.
+///
+/// Nothing is mapped to MAL's vocabulary, so the document is a faithful backup that other formats
+/// can be rendered from later. Each entry is written on its own line to keep large exports
+/// diffable.
+pub struct JsonWriter {
+    first: bool,
+}
+
+impl JsonWriter {
+    pub fn new() -> JsonWriter {
+        JsonWriter { first: true }
+    }
+}
+
+impl Default for JsonWriter {
+    fn default() -> Self {
+        JsonWriter::new()
+    }
+}
+
+impl OutputWriter for JsonWriter {
+    fn header(&mut self, f: &mut dyn Write, collection: &Collection) -> io::Result<()> {
+        let exporter = format!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        // the entries array is left open and closed by the footer
+        write!(
+            f,
+            "{{\"exporter\":{},\"type\":{},\"user\":{},\"entries\":[",
+            serde_json::to_string(&exporter)?,
+            serde_json::to_string(&collection.list_type)?,
+            serde_json::to_string(&collection.user)?,
+        )
+    }
+
+    fn entry(&mut self, f: &mut dyn Write, entry: &MediaEntry) -> io::Result<()> {
+        if !self.first {
+            write!(f, ",")?;
+        }
+        self.first = false;
+        write!(f, "\n{}", serde_json::to_string(entry)?)
+    }
+
+    fn footer(&mut self, f: &mut dyn Write) -> io::Result<()> {
+        writeln!(f, "\n]}}")
+    }
+}
@@ -0,0 +1,40 @@
+//! MAL style list exports for AniList accounts.
+//!
+//! [`AniListClient`] fetches a user's list through the official GraphQL API and [`Exporter`]
+//! turns it into the XML document MAL and AniDB import:
+//!
+//! ```no_run
+//! use mal_export_for_anilist::{AniListClient, Exporter, ListType, ANILIST_ENDPOINT};
+//!
+//! # async fn run() -> Result<(), mal_export_for_anilist::Error> {
+//! let client = AniListClient::new(ANILIST_ENDPOINT, "", 5)?;
+//! let xml = Exporter::new(ListType::Anime)
+//!     .export_string(&client, "username")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod client;
+pub mod config;
+pub mod csvformat;
+pub mod error;
+pub mod export;
+pub mod jsonformat;
+pub mod malxml;
+pub mod model;
+pub mod oauth;
+pub mod ratelimit;
+pub mod roster;
+pub mod snapshot;
+pub mod token;
+pub mod writer;
+pub mod xmlformat;
+pub mod xmlstream;
+
+pub use client::{AniListClient, Retry, RetryReason, ANILIST_ENDPOINT};
+pub use error::Error;
+pub use export::Exporter;
+pub use model::{Collection, ListSelection, ListType, MediaEntry, MediaListGroup, User, Viewer};
+pub use snapshot::Snapshot;
+pub use writer::{ModifiedEntry, OutputFormat, OutputWriter};
@@ -1,339 +1,886 @@
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::PathBuf;
-use std::{io, panic};
-
-use clap::{Parser, ValueEnum};
-use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::Client;
-use serde_json::json;
-
-mod oauth;
-mod xmlformat;
-
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-enum ListType {
-    Anime,
-    Manga,
-}
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+use mal_export_for_anilist::config::{self, Config, OAuthConfig};
+use mal_export_for_anilist::malxml::{self, Change, MalEntry, MalExport};
+use mal_export_for_anilist::oauth::{AuthCodeFlow, Token};
+use mal_export_for_anilist::roster::{self, RosterEntry};
+use mal_export_for_anilist::token::{self, StoredToken};
+use mal_export_for_anilist::{
+    oauth, snapshot, AniListClient, Collection, Error, Exporter, ListSelection, ListType,
+    ModifiedEntry, OutputFormat, Viewer, ANILIST_ENDPOINT,
+};
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    // read by load_config before clap parses the command line, only declared for --help
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        help = "Config file providing defaults for the flags [default: mal-export-for-anilist/config.toml in the config directory]"
+    )]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    export: Args,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Export lists, what running without a command does as well
+    Export(Args),
+    /// Print how many entries of each status a user's lists hold
+    Stats(StatsArgs),
+    /// Check an XML export for anything MAL or AniDB would reject
+    Validate {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Show the entries added, removed and changed between two XML exports
+    Diff {
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+    },
+    /// Log in to AniList and store the token, so that exports include hidden entries
+    Login {
+        #[command(flatten)]
+        auth: OAuthArgs,
+    },
+    /// Delete the token stored by login
+    Logout,
+}
+
+// user, list and file are required unless the config file provides them, see with_defaults
+#[derive(clap::Args)]
 struct Args {
     #[arg(short, long, help = "Username on AniList")]
-    user: String,
+    user: Option<String>,
     #[arg(short = 'l', long = "list")]
-    list_type: ListType,
-    #[arg(short, long, value_name = "FILE", help = "Example: anime-list.xml")]
-    file: PathBuf,
-    #[arg(long = "no-update", action = clap::ArgAction::SetFalse)]
+    list: Option<ListSelection>,
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        help = "Example: anime-list.xml, {user}-{list}.xml, a directory, or - for stdout"
+    )]
+    file: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Xml)]
+    format: OutputFormat,
+    #[arg(
+        long,
+        help = "Compress the export with gzip, which a --file ending in .gz does as well"
+    )]
+    gzip: bool,
+    #[arg(
+        long,
+        overrides_with = "gzip",
+        help = "Don't compress, undoing gzip in the config file"
+    )]
+    no_gzip: bool,
+    #[arg(
+        long,
+        help = "Wrap titles, comments and tags in CDATA like MAL's own exports, instead of escaping them"
+    )]
+    cdata: bool,
+    #[arg(
+        long,
+        overrides_with = "cdata",
+        help = "Escape the text, undoing cdata in the config file"
+    )]
+    no_cdata: bool,
+    #[arg(long = "no-update", action = clap::ArgAction::SetFalse, overrides_with = "force_update")]
     update: bool,
+    #[arg(
+        long = "update",
+        overrides_with = "update",
+        help = "Have the import update existing entries, undoing update = false in the config file"
+    )]
+    force_update: bool,
     #[arg(short, long, help = "Use OAuth to export hidden entries")]
     oauth: bool,
-    #[arg(long = "no-nsfw", action = clap::ArgAction::SetFalse)]
+    #[command(flatten)]
+    auth: OAuthArgs,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "oauth",
+        help = "Read the OAuth token from a file instead of prompting (the ANILIST_TOKEN environment variable works too)"
+    )]
+    token_file: Option<PathBuf>,
+    #[arg(long = "no-nsfw", action = clap::ArgAction::SetFalse, overrides_with = "force_nsfw")]
     nsfw: bool,
+    #[arg(
+        long = "nsfw",
+        overrides_with = "nsfw",
+        help = "Include adult entries, undoing nsfw = false in the config file"
+    )]
+    force_nsfw: bool,
+    #[arg(
+        long,
+        env = "ANILIST_ENDPOINT",
+        default_value = ANILIST_ENDPOINT,
+        help = "GraphQL endpoint the queries are sent to"
+    )]
+    endpoint: String,
+    #[arg(
+        long,
+        default_value_t = 5,
+        help = "How many times a rate limited or failed query is retried"
+    )]
+    max_retries: u32,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["oauth", "save_raw"],
+        help = "Export from a file saved with --save-raw or --format json instead of querying AniList"
+    )]
+    input: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Also save the API responses, to be exported again later with --input"
+    )]
+    save_raw: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["user", "input"],
+        help = "Export every user listed in a TOML, JSON or plain text file"
+    )]
+    roster: Option<PathBuf>,
 }
 
-const LIST_QUERY: &str = "
-query ($userName: String, $type: MediaType) {
-  MediaListCollection(userName: $userName, type: $type) {
-    user {
-      id
-    }
-    lists {
-      entries {
-        id
-        status
-        repeat
-        progress
-        progressVolumes
-        customLists
-        hiddenFromStatusLists
-        startedAt {
-          year
-          month
-          day
-        }
-        completedAt {
-          year
-          month
-          day
-        }
-        createdAt
-        updatedAt
-        score
-        notes
-        media {
-          idMal
-          isAdult
-          title {
-            romaji
-          }
-          format
-          episodes
-          chapters
-          volumes
+impl Args {
+    /// Lets a flag cancel the switch its opposite or the config file set. The config file only
+    /// changes the defaults, so the opposite flag is still false when the switch came from there.
+    fn resolve_switches(&mut self) {
+        self.gzip &= !self.no_gzip;
+        self.cdata &= !self.no_cdata;
+        self.update |= self.force_update;
+        self.nsfw |= self.force_nsfw;
+    }
+}
+
+// user is required unless the config file provides it, see with_defaults
+#[derive(clap::Args)]
+struct StatsArgs {
+    #[arg(short, long, help = "Username on AniList")]
+    user: Option<String>,
+    #[arg(short = 'l', long = "list", default_value = "all")]
+    list: ListSelection,
+    #[arg(
+        long,
+        env = "ANILIST_ENDPOINT",
+        default_value = ANILIST_ENDPOINT,
+        help = "GraphQL endpoint the queries are sent to"
+    )]
+    endpoint: String,
+    #[arg(
+        long,
+        default_value_t = 5,
+        help = "How many times a rate limited or failed query is retried"
+    )]
+    max_retries: u32,
+}
+
+/// The AniList API client to log in through
+#[derive(clap::Args)]
+struct OAuthArgs {
+    #[arg(
+        long,
+        env = "ANILIST_CLIENT_SECRET",
+        hide_env_values = true,
+        help = "Log in through the authorization code grant of the API client with this secret"
+    )]
+    client_secret: Option<String>,
+    #[arg(
+        long,
+        env = "ANILIST_CLIENT_ID",
+        help = "Id of the AniList API client [default: 18309]"
+    )]
+    client_id: Option<String>,
+    #[arg(
+        long,
+        env = "ANILIST_REDIRECT_URI",
+        help = "Redirect URI registered for the API client, listened on while logging in [default: http://localhost:8919/callback]"
+    )]
+    redirect_uri: Option<String>,
+    #[arg(
+        long,
+        env = "ANILIST_TOKEN_ENDPOINT",
+        help = "Where authorization codes are exchanged for tokens [default: https://anilist.co/api/v2/oauth/token]"
+    )]
+    token_endpoint: Option<String>,
+}
+
+impl OAuthArgs {
+    /// Takes what neither a flag nor the environment set from the config file.
+    fn merge(&mut self, config: &OAuthConfig) {
+        let merge = |arg: &mut Option<String>, value: &Option<String>| {
+            if arg.is_none() {
+                arg.clone_from(value);
+            }
+        };
+        merge(&mut self.client_id, &config.client_id);
+        merge(&mut self.redirect_uri, &config.redirect_uri);
+        merge(&mut self.token_endpoint, &config.token_endpoint);
+    }
+
+    fn client_id(&self) -> &str {
+        self.client_id.as_deref().unwrap_or(oauth::CLIENT_ID)
+    }
+
+    fn redirect_uri(&self) -> &str {
+        self.redirect_uri.as_deref().unwrap_or(oauth::REDIRECT_URI)
+    }
+
+    fn token_endpoint(&self) -> &str {
+        self.token_endpoint
+            .as_deref()
+            .unwrap_or(oauth::TOKEN_ENDPOINT)
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    panic::set_hook(Box::new(|p| {
+        eprintln!("Panic: {}", p);
+        std::process::exit(1);
+    }));
+
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
         }
-        priority
-      }
-      isCustomList
     }
-  }
 }
-";
-
-const ANISTATS_QUERY: &str = "
-query ($name: String) {
-  User(name: $name) {
-    id
-    name
-    statistics {
-      anime {
-        count
-        statuses {
-          status
-          count
+
+async fn run() -> Result<(), Error> {
+    let config = load_config()?;
+    let matches = with_defaults(Cli::command(), &config).get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let (mut args, export_matches) = match cli.command {
+        None => (cli.export, &matches),
+        Some(Command::Export(args)) => (
+            args,
+            matches
+                .subcommand_matches("export")
+                .expect("the export command was given"),
+        ),
+        Some(Command::Stats(args)) => return stats(args).await,
+        Some(Command::Validate { file }) => return validate(&file),
+        Some(Command::Diff { old, new }) => return diff(&old, &new),
+        Some(Command::Login { mut auth }) => {
+            auth.merge(&config.oauth);
+            return login(&auth).await;
         }
-      }
+        Some(Command::Logout) => return logout(),
+    };
+
+    // a list type from the config file is meant for querying AniList, not for checking what
+    // --input holds
+    if export_matches.value_source("list") == Some(ValueSource::DefaultValue) {
+        args.list = args.list.filter(|_| args.input.is_none());
     }
-  }
+    // the environment takes precedence over the config file, so ANILIST_TOKEN beats its token file
+    if export_matches.value_source("token_file") == Some(ValueSource::DefaultValue)
+        && env_token().is_some()
+    {
+        args.token_file = None;
+    }
+    args.resolve_switches();
+    args.auth.merge(&config.oauth);
+    export(args).await
 }
-";
-
-const MANGASTATS_QUERY: &str = "
-query ($name: String) {
-  User(name: $name) {
-    id
-    name
-    statistics {
-      manga {
-        count
-        statuses {
-          status
-          count
+
+/// Loads the file given to `--config`, or else the one in the config directory.
+///
+/// The config file provides defaults to clap, so `--config` is picked out of the command line
+/// before clap gets to parse it.
+fn load_config() -> Result<Config, Error> {
+    let mut args = std::env::args_os().skip(1);
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        } else if arg == "--config" {
+            path = args.next().map(PathBuf::from);
+        } else if let Some(value) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+            path = Some(PathBuf::from(value));
+        }
+    }
+
+    match path {
+        Some(path) if !path.is_file() => {
+            Err(Error::Config(format!("{} doesn't exist", path.display())))
         }
-      }
+        Some(path) => Config::load(&path),
+        None => match config::config_path() {
+            Some(path) => Config::load(&path),
+            None => Ok(Config::default()),
+        },
     }
-  }
-}";
+}
 
-#[derive(PartialEq)]
-enum QueryType {
-    LIST,
-    STATS,
+/// Turns the values of the config file into defaults of the flags, leaving only the flags it
+/// doesn't provide required.
+fn with_defaults(command: clap::Command, config: &Config) -> clap::Command {
+    export_defaults(command, config)
+        .mut_subcommand("export", |export| export_defaults(export, config))
+        .mut_subcommand("stats", |stats| {
+            let stats = set_defaults(stats, config);
+            if config.user.is_none() {
+                stats.mut_arg("user", |arg| arg.required(true))
+            } else {
+                stats
+            }
+        })
 }
 
-async fn make_query(
-    query: &str,
-    client: &reqwest::Client,
-    username: &String,
-    qtype: QueryType,
-    list_type: Option<&str>,
-    auth_pin: &String,
-) -> serde_json::Value {
-    let stats_query_json = json!({
-        "query" : query,
-        "variables" : {
-            "name" : username
+fn export_defaults(mut command: clap::Command, config: &Config) -> clap::Command {
+    command = set_defaults(command, config);
+    if config.user.is_none() {
+        command = command.mut_arg("user", |arg| {
+            arg.required_unless_present_any(["input", "roster"])
+        });
+    }
+    if config.list.is_none() {
+        command = command.mut_arg("list", |arg| {
+            arg.required_unless_present_any(["input", "roster"])
+        });
+    }
+    if config.file.is_none() {
+        command = command.mut_arg("file", |arg| arg.required(true));
+    }
+    command
+}
+
+/// Sets the values of the config file as the defaults of the flags `command` has.
+fn set_defaults(mut command: clap::Command, config: &Config) -> clap::Command {
+    let name = |value: Option<clap::builder::PossibleValue>| {
+        value.map(|value| value.get_name().to_string())
+    };
+    let defaults = [
+        ("user", config.user.clone()),
+        (
+            "list",
+            name(config.list.and_then(|l| l.to_possible_value())),
+        ),
+        (
+            "file",
+            config.file.as_ref().map(|p| p.display().to_string()),
+        ),
+        (
+            "format",
+            name(config.format.and_then(|f| f.to_possible_value())),
+        ),
+        ("gzip", config.gzip.map(|gzip| gzip.to_string())),
+        ("cdata", config.cdata.map(|cdata| cdata.to_string())),
+        ("update", config.update.map(|update| update.to_string())),
+        ("nsfw", config.nsfw.map(|nsfw| nsfw.to_string())),
+        ("endpoint", config.endpoint.clone()),
+        (
+            "token_file",
+            config.token_file.as_ref().map(|p| p.display().to_string()),
+        ),
+    ];
+    for (id, value) in defaults {
+        let has_arg = command.get_arguments().any(|arg| arg.get_id() == id);
+        if let Some(value) = value.filter(|_| has_arg) {
+            command = command.mut_arg(id, |arg| arg.default_value(value));
         }
-    });
-    let list_query_json = json!({
-        "query" : query,
-        "variables" : {
-            "userName" : username,
-            "type" : list_type
+    }
+    command
+}
+
+async fn export(args: Args) -> Result<(), Error> {
+    if let Some(input) = &args.input {
+        // whatever --list says, the input holds a single list and makes a single document
+        let collection = snapshot::load(input)?;
+        if let Some(list) = args
+            .list
+            .filter(|l| !l.list_types().contains(&collection.list_type))
+        {
+            return Err(Error::Input(format!(
+                "{} holds the {} list, but --list {} was given",
+                input.display(),
+                collection.list_type.media_type().to_lowercase(),
+                list.to_possible_value()
+                    .expect("no variant is skipped")
+                    .get_name()
+            )));
         }
-    });
-    let mut headers = HeaderMap::new();
-    if auth_pin != "" {
-        let headerv = HeaderValue::from_str(auth_pin);
-        match headerv {
-            Ok(_) => {}
-            Err(a) => {
-                println!("{}", a)
-            }
+        return write(
+            &args,
+            &collection,
+            &collection.user.name,
+            args.update,
+            args.nsfw,
+        );
+    }
+
+    let entries = match &args.roster {
+        Some(path) => roster::load(path)?,
+        // clap makes --user required without --input or --roster
+        None => vec![RosterEntry::new(
+            args.user.as_deref().expect("--user is required"),
+        )],
+    };
+    if let Some(entry) = entries.iter().find(|e| e.list.or(args.list).is_none()) {
+        return Err(Error::Input(format!(
+            "no list is given for {}, set one in the roster or pass --list",
+            entry.user
+        )));
+    }
+    check_templates(
+        &args,
+        entries
+            .iter()
+            .any(|e| e.list.or(args.list) == Some(ListSelection::All)),
+        args.roster.is_some(),
+    );
+
+    let (client, viewer) = connect(&args).await?;
+    if let Some(viewer) = &viewer {
+        for entry in entries
+            .iter()
+            .filter(|e| !e.user.eq_ignore_ascii_case(&viewer.name))
+        {
+            eprintln!(
+                "Warning: the token belongs to {}, hidden entries of {} won't be exported",
+                viewer.name, entry.user
+            );
         }
-        headers.insert(AUTHORIZATION, HeaderValue::from_str(auth_pin).unwrap());
     }
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    if args.roster.is_none() {
+        return export_user(&args, &client, &entries[0]).await;
+    }
 
-    let resp = client
-        .post("https://graphql.anilist.co/")
-        .headers(headers)
-        .body(if qtype == QueryType::LIST {
-            list_query_json.to_string()
-        } else {
-            stats_query_json.to_string()
-        })
-        .send()
-        .await
-        .unwrap()
-        .text()
-        .await;
-    serde_json::from_str(&resp.unwrap()).unwrap()
+    // users are exported one after the other, the shared client keeps them all within the
+    // rate limit
+    let mut results = Vec::new();
+    for entry in &entries {
+        results.push((entry, export_user(&args, &client, entry).await));
+    }
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    eprintln!(
+        "Exported {} of {} users:",
+        results.len() - failed,
+        results.len()
+    );
+    for (entry, result) in &results {
+        match result {
+            Ok(()) => eprintln!("  {}: ok", entry.user),
+            Err(e) => eprintln!("  {}: failed, {}", entry.user, e),
+        }
+    }
+    if failed > 0 {
+        return Err(Error::Batch {
+            failed,
+            total: results.len(),
+        });
+    }
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() -> std::io::Result<()> {
-    panic::set_hook(Box::new(|p| {
-        eprintln!("Panic: {}", p);
-        std::process::exit(1);
-    }));
+/// Exits with a usage error when `--file` or `--save-raw` can't name the file of every list
+/// being exported.
+fn check_templates(args: &Args, several_lists: bool, several_users: bool) {
+    for (flag, path) in [
+        ("--file", args.file.as_ref()),
+        ("--save-raw", args.save_raw.as_ref()),
+    ] {
+        let Some(path) = path.filter(|path| !path.is_dir()) else {
+            continue;
+        };
+        let reason = if several_lists {
+            "--list all"
+        } else {
+            "--roster"
+        };
+        if is_stdout(path) && flag == "--file" {
+            if several_lists || several_users {
+                Cli::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "{} writes several documents, which can't all go to stdout",
+                            reason
+                        ),
+                    )
+                    .exit();
+            }
+            continue;
+        }
+        let template = path.to_str().unwrap_or_default();
+        let missing = if several_lists && !template.contains("{list}") {
+            "{list}"
+        } else if several_users && !template.contains("{user}") {
+            "{user}"
+        } else {
+            continue;
+        };
+        Cli::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "{} needs {} to be a directory or to contain {}",
+                    reason, flag, missing
+                ),
+            )
+            .exit();
+    }
+}
 
-    let args = Args::parse();
-    let client = Client::new();
+/// Creates the client the queries are sent through, along with the owner of its token if it has
+/// one.
+async fn connect(args: &Args) -> Result<(AniListClient, Option<Viewer>), Error> {
     let mut auth_pin: String = String::new();
     if args.oauth {
-        println!("OAuth was enabled, please visit and authenticate through the following link in your browser: {}", oauth::gen_url("18309"));
+        auth_pin.push_str("Bearer ");
+        auth_pin.push_str(&authorize(&args.auth).await?.access_token);
+    } else if let Some(path) = &args.token_file {
+        auth_pin.push_str("Bearer ");
+        auth_pin.push_str(&token::read_token_file(path)?);
+    } else if let Some(token) = env_token() {
+        auth_pin.push_str("Bearer ");
+        auth_pin.push_str(&token);
+    } else if let Some(path) = token::token_path() {
+        match StoredToken::load(&path)? {
+            Some(stored) if stored.is_expired() => eprintln!(
+                "The token stored by login expired on {}, log in again to export hidden entries",
+                stored.expires().format("%Y-%m-%d")
+            ),
+            Some(stored) => {
+                auth_pin.push_str("Bearer ");
+                auth_pin.push_str(&stored.access_token);
+            }
+            None => {}
+        }
+    }
+    let client = AniListClient::new(&args.endpoint, &auth_pin, args.max_retries)?
+        .on_retry(|retry| eprintln!("{}", retry));
+    if auth_pin.is_empty() {
+        return Ok((client, None));
+    }
+
+    // a rejected token would otherwise only surface once the export queries fail
+    let viewer = client.viewer().await?;
+    eprintln!("Authorized as {}", viewer.name);
+    Ok((client, Some(viewer)))
+}
+
+/// The token in `ANILIST_TOKEN`. There's deliberately no flag for it, since command lines are
+/// visible to every user of the machine.
+fn env_token() -> Option<String> {
+    std::env::var("ANILIST_TOKEN")
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// Has the user authorize the program in their browser and returns the access token.
+async fn authorize(auth: &OAuthArgs) -> Result<Token, Error> {
+    let Some(client_secret) = &auth.client_secret else {
+        // without a secret only the implicit grant is possible, which leaves it to the user to
+        // bring the token over
+        eprintln!("OAuth was enabled, please visit and authenticate through the following link in your browser: {}", oauth::gen_url(auth.client_id()));
         let mut input = String::new();
         io::stdin()
             .read_line(&mut input)
-            .expect("Failed to read input");
-        let input = input.trim();
-        auth_pin.push_str("Bearer ");
-        auth_pin.push_str(&input);
-    }
-
-    let path: &str = args.file.to_str().expect("couldn't decode file path");
-    // create file / flush contents of an existing file
-    #[allow(unused_assignments)]
-    let mut f = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(path)?;
-    f = OpenOptions::new().write(true).append(true).open(path)?;
-
-    #[allow(unused_assignments)]
-    let mut result: serde_json::Value = json!(null);
-    let pre_user_statistics: Result<xmlformat::UserStatistics, _> = match args.list_type {
-        ListType::Anime => {
-            result = make_query(
-                ANISTATS_QUERY,
-                &client,
-                &args.user,
-                QueryType::STATS,
-                None,
-                &auth_pin,
-            )
-            .await;
-            serde_json::from_value(result["data"]["User"]["statistics"]["anime"].to_owned())
-        }
-        ListType::Manga => {
-            result = make_query(
-                MANGASTATS_QUERY,
-                &client,
-                &args.user,
-                QueryType::STATS,
-                None,
-                &auth_pin,
-            )
-            .await;
-            serde_json::from_value(result["data"]["User"]["statistics"]["manga"].to_owned())
-        }
+            .map_err(|e| Error::OAuth(format!("couldn't read the token from stdin: {}", e)))?;
+        return Ok(Token {
+            access_token: input.trim().to_string(),
+            expires_in: None,
+        });
     };
-    match pre_user_statistics {
-        Ok(_) => {}
-        Err(_) => {
-            panic!("OAuth token usage failed")
-        }
+
+    let flow = AuthCodeFlow {
+        client_id: auth.client_id().to_string(),
+        client_secret: client_secret.clone(),
+        redirect_uri: auth.redirect_uri().to_string(),
+        token_endpoint: auth.token_endpoint().to_string(),
     };
-    let user_statistics =
-        pre_user_statistics.expect("an error has occured while parsing UserStatistics from API");
-
-    // header
-    writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
-    writeln!(f, "<myanimelist>")?;
-    writeln!(f, "{}", xmlformat::xml_export_comment(&args.user))?;
-    writeln!(f, "\t<myinfo>")?;
-    writeln!(f, "{}", {
-        match args.list_type {
-            ListType::Anime => xmlformat::xml_animeheader(
-                user_statistics,
-                result["data"]["User"]["id"].as_u64().unwrap(),
-                result["data"]["User"]["name"].as_str().unwrap().to_string(),
-            ),
-            ListType::Manga => xmlformat::xml_mangaheader(
-                user_statistics,
-                result["data"]["User"]["id"].as_u64().unwrap(),
-                result["data"]["User"]["name"].as_str().unwrap().to_string(),
-            ),
-        }
+    let listener = flow.listen().await?;
+    eprintln!(
+        "OAuth was enabled, please visit and authenticate through the following link in your browser: {}",
+        flow.authorize_url(&listener)
+    );
+    let code = listener.code().await?;
+    flow.exchange(&code).await
+}
+
+async fn login(auth: &OAuthArgs) -> Result<(), Error> {
+    let path = token::token_path().ok_or_else(|| {
+        Error::OAuth(
+            "there's no config directory to store the token in, set $XDG_CONFIG_HOME".to_string(),
+        )
     })?;
-    writeln!(f, "\t</myinfo>")?;
-
-    let mut status_media_list: Vec<xmlformat::MediaEntry> = Vec::new();
-    let mut custom_media_list: Vec<xmlformat::MediaEntry> = Vec::new();
-    let result = match args.list_type {
-        ListType::Anime => {
-            make_query(
-                LIST_QUERY,
-                &client,
-                &args.user,
-                QueryType::LIST,
-                Some("ANIME"),
-                &auth_pin,
-            )
-            .await
+    let stored = StoredToken::new(&authorize(auth).await?);
+    stored.save(&path)?;
+    eprintln!(
+        "Logged in, the token is stored in {} until {}",
+        path.display(),
+        stored.expires().format("%Y-%m-%d")
+    );
+    Ok(())
+}
+
+fn logout() -> Result<(), Error> {
+    match token::token_path() {
+        Some(path) if StoredToken::remove(&path)? => {
+            eprintln!("Logged out, {} was deleted", path.display())
         }
-        ListType::Manga => {
-            make_query(
-                LIST_QUERY,
-                &client,
-                &args.user,
-                QueryType::LIST,
-                Some("MANGA"),
-                &auth_pin,
-            )
-            .await
+        _ => eprintln!("Not logged in"),
+    }
+    Ok(())
+}
+
+async fn export_user(
+    args: &Args,
+    client: &AniListClient,
+    entry: &RosterEntry,
+) -> Result<(), Error> {
+    let list = entry.list.or(args.list).expect("checked before connecting");
+    for collection in fetch(args, client, &entry.user, list).await? {
+        write(
+            args,
+            &collection,
+            &entry.user,
+            entry.update.unwrap_or(args.update),
+            entry.nsfw.unwrap_or(args.nsfw),
+        )?;
+    }
+    Ok(())
+}
+
+async fn fetch(
+    args: &Args,
+    client: &AniListClient,
+    user: &str,
+    list: ListSelection,
+) -> Result<Vec<Collection>, Error> {
+    let snapshots = match list {
+        ListSelection::Anime => vec![client.snapshot(user, ListType::Anime).await?],
+        ListSelection::Manga => vec![client.snapshot(user, ListType::Manga).await?],
+        ListSelection::All => {
+            let (anime, manga) = tokio::try_join!(
+                client.snapshot(user, ListType::Anime),
+                client.snapshot(user, ListType::Manga)
+            )?;
+            vec![anime, manga]
         }
     };
 
-    let lists: Vec<xmlformat::MediaListGroup> =
-        serde_json::from_value::<Vec<xmlformat::MediaListGroup>>(
-            result["data"]["MediaListCollection"]["lists"].clone(),
-        )
-        .expect("unexpected error occured while parsing user lists");
+    let mut collections = Vec::new();
+    for snapshot in snapshots {
+        if let Some(path) = &args.save_raw {
+            snapshot.save(&output_path(
+                path,
+                user,
+                snapshot.list_type,
+                "{user}-{list}-raw.json",
+            ))?;
+        }
+        collections.push(snapshot.collection()?);
+    }
+    Ok(collections)
+}
 
-    for list in &lists {
-        if list.isCustomList {
-            custom_media_list.extend(list.entries.clone())
+fn write(
+    args: &Args,
+    collection: &Collection,
+    user: &str,
+    update: bool,
+    nsfw: bool,
+) -> Result<(), Error> {
+    // clap only lets --file be left out along with a subcommand
+    let file = args.file.as_deref().expect("--file is required");
+    let exporter = Exporter {
+        list_type: collection.list_type,
+        format: args.format,
+        update,
+        nsfw,
+        cdata: args.cdata,
+    };
+    let write_to = |mut f: &mut dyn Write, gzip: bool| {
+        if gzip {
+            exporter.write_gzip(collection, &mut f)
         } else {
-            status_media_list.extend(list.entries.clone())
+            exporter.write(collection, &mut f)
         }
+    };
+    if is_stdout(file) {
+        let modified = write_to(&mut BufWriter::new(io::stdout().lock()), args.gzip)?;
+        warn_modified(&modified);
+        return Ok(());
     }
 
-    for media_entry in status_media_list {
-        if !(args.nsfw == false && media_entry.media.isAdult == true) {
-            match args.list_type {
-                ListType::Anime => {
-                    writeln!(f, "{}", xmlformat::xml_anime(media_entry, args.update))?
-                }
-                ListType::Manga => {
-                    writeln!(f, "{}", xmlformat::xml_manga(media_entry, args.update))?
-                }
-            }
+    let path = output_path(
+        file,
+        user,
+        collection.list_type,
+        &format!(
+            "{{user}}-{{list}}.{}{}",
+            args.format.extension(),
+            if args.gzip { ".gz" } else { "" }
+        ),
+    );
+    let gzip = args.gzip || path.extension().is_some_and(|extension| extension == "gz");
+    let modified = write_atomically(&path, |f| write_to(f, gzip))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    warn_modified(&modified);
+    Ok(())
+}
+
+fn warn_modified(modified: &[ModifiedEntry]) {
+    for entry in modified {
+        eprintln!(
+            "Warning: removed characters XML doesn't allow from the {} of {} (AniList id {})",
+            entry.fields.join(", "),
+            entry.title,
+            entry.id
+        );
+    }
+}
+
+/// Writes to a temporary file next to `path` and renames it over `path` once `write` is done, so
+/// a failed export leaves the previous one untouched.
+fn write_atomically<T>(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<T>,
+) -> io::Result<T> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+    let result = File::create(&temp).and_then(|f| {
+        if let Ok(metadata) = fs::metadata(path) {
+            // keeps the permissions of the export being replaced
+            f.set_permissions(metadata.permissions())?;
         }
+        let mut f = BufWriter::new(f);
+        let written = write(&mut f)?;
+        f.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temp, path)?;
+        Ok(written)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
     }
-    for media_entry in custom_media_list {
-        if media_entry.hiddenFromStatusLists
-            && !(args.nsfw == false && media_entry.media.isAdult == true)
-        {
-            match args.list_type {
-                ListType::Anime => {
-                    writeln!(f, "{}", xmlformat::xml_anime(media_entry, args.update))?
-                }
-                ListType::Manga => {
-                    writeln!(f, "{}", xmlformat::xml_manga(media_entry, args.update))?
+    result
+}
+
+async fn stats(args: StatsArgs) -> Result<(), Error> {
+    let user = args.user.as_deref().expect("--user is required");
+    let client = AniListClient::new(&args.endpoint, "", args.max_retries)?
+        .on_retry(|retry| eprintln!("{}", retry));
+    for list_type in args.list.list_types() {
+        let stats = client.user(user, *list_type).await?;
+        println!(
+            "{}'s {} list: {} entries",
+            stats.name,
+            list_type.media_type().to_lowercase(),
+            stats.statistics.count
+        );
+        for status in &stats.statistics.statuses {
+            println!(
+                "  {:<10} {}",
+                format!("{:?}", status.status).to_lowercase(),
+                status.count
+            );
+        }
+    }
+    Ok(())
+}
+
+fn validate(file: &Path) -> Result<(), Error> {
+    let export = MalExport::read(file)?;
+    let problems = export.problems();
+    println!(
+        "{}: {} entries, {} left commented out because MAL doesn't know them",
+        file.display(),
+        export.entries.len(),
+        export.commented
+    );
+    for problem in &problems {
+        println!("  {}", problem);
+    }
+    if !problems.is_empty() {
+        return Err(Error::Invalid(problems.len()));
+    }
+    println!("Ready to import");
+    Ok(())
+}
+
+fn diff(old: &Path, new: &Path) -> Result<(), Error> {
+    let (old, new) = (MalExport::read(old)?, MalExport::read(new)?);
+    let changes = malxml::diff(&old, &new);
+    let name = |entry: &MalEntry| {
+        format!(
+            "[{}] {}",
+            entry.id().unwrap_or_default(),
+            entry.title().unwrap_or_default()
+        )
+    };
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in &changes {
+        match change {
+            Change::Added(entry) => {
+                added += 1;
+                println!("+ {}", name(entry));
+            }
+            Change::Removed(entry) => {
+                removed += 1;
+                println!("- {}", name(entry));
+            }
+            Change::Changed(entry, fields) => {
+                changed += 1;
+                println!("~ {}", name(entry));
+                for (field, was, is) in fields {
+                    println!("    {}: {:?} -> {:?}", field, was, is);
                 }
             }
         }
     }
-    writeln!(f, "</myanimelist>")?;
+    println!("{} added, {} removed, {} changed", added, removed, changed);
+    Ok(())
+}
 
-    f.flush()?;
-    drop(f);
+/// `--file -` streams the export to stdout, leaving stderr for everything else.
+fn is_stdout(path: &Path) -> bool {
+    path == Path::new("-")
+}
 
-    Ok(())
+/// Fills in the `{user}` and `{list}` placeholders of `path`, or names the file after
+/// `default_name` when `path` is a directory.
+fn output_path(path: &Path, user: &str, list_type: ListType, default_name: &str) -> PathBuf {
+    let list = list_type.media_type().to_lowercase();
+    let fill = |template: &str| template.replace("{user}", user).replace("{list}", &list);
+    if path.is_dir() {
+        path.join(fill(default_name))
+    } else {
+        match path.to_str() {
+            Some(template) => PathBuf::from(fill(template)),
+            None => path.to_path_buf(),
+        }
+    }
 }
@@ -0,0 +1,317 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::error::Error;
+use crate::model::ListType;
+
+const ANIME_STATUSES: [&str; 5] = [
+    "Watching",
+    "Completed",
+    "On-Hold",
+    "Dropped",
+    "Plan to Watch",
+];
+const MANGA_STATUSES: [&str; 5] = ["Reading", "Completed", "On-Hold", "Dropped", "Plan to Read"];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A MAL style XML export, read back for `validate` and `diff`.
+#[derive(Clone, Debug, Default)]
+pub struct MalExport {
+    /// name of the root element, `myanimelist` in a valid export
+    pub root: Option<String>,
+    pub myinfo: BTreeMap<String, String>,
+    pub entries: Vec<MalEntry>,
+    /// entries left commented out because MAL doesn't know them
+    pub commented: usize,
+}
+
+/// One `<anime>` or `<manga>` element, with the text of each of its children.
+#[derive(Clone, Debug)]
+pub struct MalEntry {
+    pub list_type: ListType,
+    pub fields: BTreeMap<String, String>,
+}
+
+impl MalEntry {
+    pub fn id(&self) -> Option<&str> {
+        self.field(match self.list_type {
+            ListType::Anime => "series_animedb_id",
+            ListType::Manga => "manga_mangadb_id",
+        })
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.field(match self.list_type {
+            ListType::Anime => "series_title",
+            ListType::Manga => "manga_title",
+        })
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+}
+
+impl MalExport {
+    /// Reads the export at `path`, gzip compressed or not, failing only if it isn't well-formed
+    /// XML.
+    pub fn read(path: &Path) -> Result<MalExport, Error> {
+        let read = |path: &Path| -> io::Result<String> {
+            let bytes = fs::read(path)?;
+            let mut xml = String::new();
+            if bytes.starts_with(&GZIP_MAGIC) {
+                GzDecoder::new(bytes.as_slice()).read_to_string(&mut xml)?;
+            } else {
+                xml = String::from_utf8(bytes)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            }
+            Ok(xml)
+        };
+        let xml = read(path)
+            .map_err(|e| Error::Input(format!("couldn't read {}: {}", path.display(), e)))?;
+        MalExport::parse(&xml)
+            .map_err(|e| Error::Input(format!("{} isn't well-formed XML: {}", path.display(), e)))
+    }
+
+    pub fn parse(xml: &str) -> Result<MalExport, String> {
+        let mut reader = Reader::from_str(xml);
+        reader.config_mut().trim_text(true);
+
+        let mut export = MalExport::default();
+        // the open elements, the root first
+        let mut path: Vec<String> = Vec::new();
+        let mut entry: Option<MalEntry> = None;
+        let mut text = String::new();
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| format!("{} (at byte {})", e, reader.error_position()))?;
+            match event {
+                Event::Start(start) => {
+                    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+                    match path.len() {
+                        0 => export.root = Some(name.clone()),
+                        1 if name == "anime" || name == "manga" => {
+                            entry = Some(MalEntry {
+                                list_type: if name == "anime" {
+                                    ListType::Anime
+                                } else {
+                                    ListType::Manga
+                                },
+                                fields: BTreeMap::new(),
+                            })
+                        }
+                        _ => {}
+                    }
+                    path.push(name);
+                    text.clear();
+                }
+                Event::Empty(empty) if path.len() == 2 => {
+                    let name = String::from_utf8_lossy(empty.name().as_ref()).into_owned();
+                    insert_field(&path, &mut export, &mut entry, name, String::new());
+                }
+                Event::Text(t) => {
+                    text.push_str(&t.unescape().map_err(|e| e.to_string())?);
+                }
+                Event::CData(c) => text.push_str(&String::from_utf8_lossy(&c.into_inner())),
+                Event::End(_) => {
+                    let name = path.pop().unwrap_or_default();
+                    if path.len() == 2 {
+                        insert_field(&path, &mut export, &mut entry, name, text.clone());
+                    } else if path.len() == 1 {
+                        export.entries.extend(entry.take());
+                    }
+                    text.clear();
+                }
+                Event::Comment(comment) => {
+                    let comment = String::from_utf8_lossy(&comment);
+                    if comment.contains("<anime>") || comment.contains("<manga>") {
+                        export.commented += 1;
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(export)
+    }
+
+    /// The list type `myinfo` declares.
+    pub fn list_type(&self) -> Option<ListType> {
+        match self.myinfo.get("user_export_type").map(String::as_str) {
+            Some("1") => Some(ListType::Anime),
+            Some("2") => Some(ListType::Manga),
+            _ => None,
+        }
+    }
+
+    /// Everything that would make MAL or AniDB reject or misread the export.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.root.as_deref() != Some("myanimelist") {
+            problems.push(format!(
+                "the root element is <{}> instead of <myanimelist>",
+                self.root.as_deref().unwrap_or_default()
+            ));
+        }
+        let list_type = self.list_type();
+        if list_type.is_none() {
+            problems.push("<myinfo> doesn't declare a user_export_type of 1 or 2".to_string());
+        }
+
+        let mut ids = HashSet::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let name = match (entry.id(), entry.title()) {
+                (Some(id), Some(title)) => format!("{} ({})", title, id),
+                (Some(id), None) => id.to_string(),
+                _ => format!("entry {}", index + 1),
+            };
+            if list_type.is_some_and(|list_type| list_type != entry.list_type) {
+                problems.push(format!(
+                    "{} is an <{}> entry, but <myinfo> declares a {} list",
+                    name,
+                    media_name(entry.list_type),
+                    media_name(list_type.unwrap_or(entry.list_type))
+                ));
+            }
+            match entry.id() {
+                None => problems.push(format!("{} has no MAL id", name)),
+                Some(id) if id.parse::<u64>().map_or(true, |id| id == 0) => {
+                    problems.push(format!("{} has the invalid MAL id {:?}", name, id))
+                }
+                Some(id) if !ids.insert(id) => {
+                    problems.push(format!("{} appears more than once", name))
+                }
+                Some(_) => {}
+            }
+
+            let statuses = match entry.list_type {
+                ListType::Anime => ANIME_STATUSES,
+                ListType::Manga => MANGA_STATUSES,
+            };
+            match entry.field("my_status") {
+                Some(status) if statuses.contains(&status) => {}
+                Some(status) => {
+                    problems.push(format!("{} has the unknown status {:?}", name, status))
+                }
+                None => problems.push(format!("{} has no status", name)),
+            }
+            if let Some(score) = entry.field("my_score") {
+                // MAL scores out of 10, which AniList users on the 100 point scale exceed
+                if score
+                    .parse::<f32>()
+                    .map_or(true, |score| !(0.0..=10.0).contains(&score))
+                {
+                    problems.push(format!("{} has the invalid score {:?}", name, score));
+                }
+            }
+            for field in ["my_start_date", "my_finish_date"] {
+                if let Some(date) = entry.field(field).filter(|date| !is_mal_date(date)) {
+                    problems.push(format!("{} has the invalid {} {:?}", name, field, date));
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// How an entry differs between two exports.
+#[derive(Debug)]
+pub enum Change<'a> {
+    Added(&'a MalEntry),
+    Removed(&'a MalEntry),
+    /// the entry of the new export, with every field that differs as `(field, old, new)`
+    Changed(&'a MalEntry, Vec<(&'a str, &'a str, &'a str)>),
+}
+
+/// Compares the entries of two exports by MAL id, in the order of the new export followed by the
+/// removed entries. Entries without an id can't be matched up and are left out.
+pub fn diff<'a>(old: &'a MalExport, new: &'a MalExport) -> Vec<Change<'a>> {
+    let by_id = |export: &'a MalExport| -> BTreeMap<(ListType, &'a str), &'a MalEntry> {
+        export
+            .entries
+            .iter()
+            .filter_map(|entry| Some(((entry.list_type, entry.id()?), entry)))
+            .collect()
+    };
+    let old_entries = by_id(old);
+    let new_entries = by_id(new);
+
+    let mut changes = Vec::new();
+    for entry in &new.entries {
+        let Some(id) = entry.id() else { continue };
+        let Some(before) = old_entries.get(&(entry.list_type, id)) else {
+            changes.push(Change::Added(entry));
+            continue;
+        };
+        let fields: Vec<_> = before
+            .fields
+            .keys()
+            .chain(entry.fields.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter_map(|field| {
+                let (was, is) = (before.field(field), entry.field(field));
+                (was != is).then(|| {
+                    (
+                        field.as_str(),
+                        was.unwrap_or_default(),
+                        is.unwrap_or_default(),
+                    )
+                })
+            })
+            .collect();
+        if !fields.is_empty() {
+            changes.push(Change::Changed(entry, fields));
+        }
+    }
+    for entry in &old.entries {
+        if let Some(id) = entry.id() {
+            if !new_entries.contains_key(&(entry.list_type, id)) {
+                changes.push(Change::Removed(entry));
+            }
+        }
+    }
+    changes
+}
+
+fn insert_field(
+    path: &[String],
+    export: &mut MalExport,
+    entry: &mut Option<MalEntry>,
+    name: String,
+    text: String,
+) {
+    match (path[1].as_str(), entry) {
+        ("myinfo", _) => {
+            export.myinfo.insert(name, text);
+        }
+        (_, Some(entry)) => {
+            entry.fields.insert(name, text);
+        }
+        _ => {}
+    }
+}
+
+fn media_name(list_type: ListType) -> &'static str {
+    match list_type {
+        ListType::Anime => "anime",
+        ListType::Manga => "manga",
+    }
+}
+
+/// `YYYY-MM-DD`, where unknown parts are zeroes
+fn is_mal_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    parts.len() == 3
+        && [4, 2, 2]
+            .iter()
+            .zip(&parts)
+            .all(|(len, part)| part.len() == *len && part.bytes().all(|b| b.is_ascii_digit()))
+}
@@ -0,0 +1,159 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ListType {
+    Anime,
+    Manga,
+}
+
+impl ListType {
+    /// The MediaType enum value AniList expects for this list
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            ListType::Anime => "ANIME",
+            ListType::Manga => "MANGA",
+        }
+    }
+}
+
+/// Which lists of a user to export.
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ListSelection {
+    Anime,
+    Manga,
+    /// both lists, written to two files
+    All,
+}
+
+impl ListSelection {
+    pub fn list_types(self) -> &'static [ListType] {
+        match self {
+            ListSelection::Anime => &[ListType::Anime],
+            ListSelection::Manga => &[ListType::Manga],
+            ListSelection::All => &[ListType::Anime, ListType::Manga],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Status {
+    CURRENT,
+    PLANNING,
+    COMPLETED,
+    DROPPED,
+    PAUSED,
+    REPEATING,
+}
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+pub enum Format {
+    TV,
+    TV_SHORT,
+    MOVIE,
+    SPECIAL,
+    OVA,
+    ONA,
+    MUSIC,
+    MANGA,
+    NOVEL,
+    ONE_SHOT,
+    UNKNOWN, // reserved because of Rust
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Date {
+    pub year: Option<u32>,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+}
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Title {
+    pub romaji: String,
+}
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[allow(non_snake_case)]
+pub struct Media {
+    pub id: u64,
+    pub idMal: Option<u64>,
+    pub isAdult: bool,
+    pub title: Title,
+    pub format: Option<Format>,
+    pub episodes: Option<u64>,
+    pub chapters: Option<u64>,
+    pub volumes: Option<u64>,
+}
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct StatusEntry {
+    pub status: Status,
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[allow(non_snake_case)]
+pub struct MediaEntry {
+    pub id: u64,
+    pub status: Status,
+    pub repeat: u64,
+    pub progress: u64,
+    pub progressVolumes: Option<u64>,
+    pub customLists: serde_json::Value,
+    pub hiddenFromStatusLists: bool,
+    pub startedAt: Date,
+    pub completedAt: Date,
+    pub createdAt: Option<u64>,
+    pub updatedAt: Option<u64>,
+    pub score: f32,
+    pub notes: Option<String>,
+    pub media: Media,
+    pub priority: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[allow(non_snake_case)]
+pub struct MediaListGroup {
+    pub entries: Vec<MediaEntry>,
+    pub isCustomList: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserStatistics {
+    pub count: u64,
+    pub statuses: Vec<StatusEntry>,
+}
+
+impl UserStatistics {
+    /// Number of entries with the given status, 0 if AniList didn't report it
+    pub fn count_of(&self, status: Status) -> u64 {
+        self.statuses
+            .iter()
+            .find(|s| s.status == status)
+            .map_or(0, |s| s.count)
+    }
+}
+
+/// An AniList user along with the statistics of one of their lists
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct User {
+    pub id: u64,
+    pub name: String,
+    pub statistics: UserStatistics,
+}
+
+/// The owner of the token queries are sent with
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Viewer {
+    pub id: u64,
+    pub name: String,
+}
+
+/// Everything an export is built from: the user and their list of one type
+#[derive(Clone, Debug)]
+pub struct Collection {
+    pub list_type: ListType,
+    pub user: User,
+    pub lists: Vec<MediaListGroup>,
+}
@@ -1,6 +1,243 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::Duration;
+
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+
+use crate::error::Error;
+
+/// The API client of this program, registered by its author
+pub const CLIENT_ID: &str = "18309";
+pub const AUTHORIZE_URL: &str = "https://anilist.co/api/v2/oauth/authorize";
+pub const TOKEN_ENDPOINT: &str = "https://anilist.co/api/v2/oauth/token";
+pub const REDIRECT_URI: &str = "http://localhost:8919/callback";
+
+/// How long the redirect listener waits for the browser before giving up
+const REDIRECT_TIMEOUT: Duration = Duration::from_secs(300);
+
+const REDIRECT_PAGE: &str =
+    "<!DOCTYPE html>\n<html><body><p>Authorized, you can close this tab now.</p></body></html>\n";
+
+/// Builds the URL of the implicit grant, which shows the token to the user for them to paste.
 pub fn gen_url(id: &str) -> String {
-    format!(
-        "https://anilist.co/api/v2/oauth/authorize?client_id={}&response_type=token",
-        id
-    )
+    format!("{}?client_id={}&response_type=token", AUTHORIZE_URL, id)
+}
+
+/// What the token endpoint answers with.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Token {
+    pub access_token: String,
+    /// seconds until the token expires, AniList tokens last a year
+    pub expires_in: Option<u64>,
+}
+
+/// The authorization code grant, for API clients registered with a localhost redirect URI.
+///
+/// The browser is sent to `authorize_url`, AniList redirects it back to `redirect_uri` where a
+/// temporary listener picks up the code, and the code is exchanged for a token at
+/// `token_endpoint`.
+pub struct AuthCodeFlow {
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    pub token_endpoint: String,
+}
+
+impl AuthCodeFlow {
+    /// Starts listening for the redirect. This has to happen before the user is sent to
+    /// `authorize_url`, so that the redirect can't arrive before anyone is listening.
+    pub async fn listen(&self) -> Result<RedirectListener, Error> {
+        let uri = Url::parse(&self.redirect_uri).map_err(|e| {
+            Error::OAuth(format!("invalid redirect URI {}: {}", self.redirect_uri, e))
+        })?;
+        let host = uri.host_str().unwrap_or("localhost");
+        let port = uri.port_or_known_default().unwrap_or(80);
+        let listener = TcpListener::bind((host, port)).await.map_err(|e| {
+            Error::OAuth(format!(
+                "couldn't listen on {}:{} for the redirect: {}",
+                host, port, e
+            ))
+        })?;
+        Ok(RedirectListener {
+            listener,
+            path: uri.path().to_string(),
+            state: random_state(),
+        })
+    }
+
+    pub fn authorize_url(&self, listener: &RedirectListener) -> String {
+        let mut url = Url::parse(AUTHORIZE_URL).expect("the authorize URL is valid");
+        url.query_pairs_mut()
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("response_type", "code")
+            .append_pair("state", &listener.state);
+        url.into()
+    }
+
+    /// Trades the code the redirect carried for an access token.
+    pub async fn exchange(&self, code: &str) -> Result<Token, Error> {
+        let resp = reqwest::Client::new()
+            .post(&self.token_endpoint)
+            .header(ACCEPT, "application/json")
+            .header(CONTENT_TYPE, "application/json")
+            .body(
+                json!({
+                "grant_type": "authorization_code",
+                "client_id": self.client_id,
+                "client_secret": self.client_secret,
+                "redirect_uri": self.redirect_uri,
+                "code": code,
+                })
+                .to_string(),
+            )
+            .send()
+            .await?;
+        let status = resp.status();
+        let body = resp.text().await?;
+        if !status.is_success() {
+            return Err(Error::OAuth(format!(
+                "the token endpoint answered with HTTP {}: {}",
+                status,
+                body.trim()
+            )));
+        }
+        serde_json::from_str(&body).map_err(|e| Error::Parse {
+            what: "the token endpoint's answer",
+            source: e,
+        })
+    }
+}
+
+/// A temporary HTTP listener on the redirect URI.
+pub struct RedirectListener {
+    listener: TcpListener,
+    path: String,
+    state: String,
+}
+
+impl RedirectListener {
+    /// Waits for the browser to be redirected back and returns the authorization code.
+    ///
+    /// Requests to other paths (browsers like to ask for a favicon) are answered with a 404 and
+    /// otherwise ignored.
+    pub async fn code(self) -> Result<String, Error> {
+        match tokio::time::timeout(REDIRECT_TIMEOUT, self.accept()).await {
+            Ok(Err(Error::Io(e))) => {
+                Err(Error::OAuth(format!("the redirect listener failed: {}", e)))
+            }
+            Ok(result) => result,
+            Err(_) => Err(Error::OAuth(format!(
+                "no authorization arrived within {} minutes",
+                REDIRECT_TIMEOUT.as_secs() / 60
+            ))),
+        }
+    }
+
+    /// Serves every connection in a task of its own, so that one the browser opened ahead of time
+    /// and left idle can't hold up the redirect arriving on another.
+    async fn accept(&self) -> Result<String, Error> {
+        // dropping the set aborts the connections still open once the redirect came
+        let mut answers = JoinSet::new();
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, _) = accepted?;
+                    let path = self.path.clone();
+                    let state = self.state.clone();
+                    answers.spawn(async move { answer(stream, &path, &state).await });
+                }
+                Some(answered) = answers.join_next() => {
+                    // a connection failing halfway only loses its own answer
+                    if let Ok(Ok(Some(result))) = answered {
+                        return result;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reads one request and answers it, returning what the redirect carried if it was one.
+async fn answer(
+    mut stream: TcpStream,
+    path: &str,
+    state: &str,
+) -> io::Result<Option<Result<String, Error>>> {
+    let mut buf = vec![0; 8192];
+    let mut len = 0;
+    // only the request line matters, but the whole head is read since closing the
+    // connection on unread data resets it before the browser gets to show the answer
+    while !buf[..len].windows(4).any(|w| w == b"\r\n\r\n") && len < buf.len() {
+        match stream.read(&mut buf[len..]).await? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let target = request.split(' ').nth(1).unwrap_or("/");
+    let url = Url::parse("http://localhost")
+        .and_then(|base| base.join(target))
+        .ok()
+        .filter(|url| url.path() == path);
+    let Some(url) = url else {
+        stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .await?;
+        return Ok(None);
+    };
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    let result = if let Some(error) = param("error") {
+        Err(Error::OAuth(format!(
+            "AniList refused the authorization: {}",
+            param("error_description").unwrap_or(error)
+        )))
+    } else if param("state").as_deref() != Some(state) {
+        Err(Error::OAuth(
+            "the redirect doesn't belong to this login attempt".to_string(),
+        ))
+    } else {
+        param("code").ok_or_else(|| Error::OAuth("the redirect carries no code".to_string()))
+    };
+
+    let page = match &result {
+        Ok(_) => REDIRECT_PAGE.to_string(),
+        Err(e) => format!("{}\n", e),
+    };
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                if result.is_ok() { "200 OK" } else { "400 Bad Request" },
+                page.len(),
+                page
+            )
+            .as_bytes(),
+        )
+        .await?;
+    Ok(Some(result))
+}
+
+/// An unguessable value tying the redirect to this login attempt
+fn random_state() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    format!("{:016x}", hasher.finish())
 }
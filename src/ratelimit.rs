@@ -0,0 +1,89 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+
+const RATELIMIT_REMAINING: &str = "x-ratelimit-remaining";
+const RATELIMIT_RESET: &str = "x-ratelimit-reset";
+
+// AniList's limit is per minute, so this is the longest a full window can take to reset
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Keeps queries under AniList's rate limit and decides how long to back off before retrying.
+///
+/// The limiter is shared by every query made through it, so a 429 seen by one query pauses the
+/// ones after it as well.
+pub struct RateLimiter {
+    pub max_retries: u32,
+    base_delay: Duration,
+    resume_at: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_retries: u32) -> RateLimiter {
+        RateLimiter {
+            max_retries,
+            base_delay: Duration::from_secs(1),
+            resume_at: Mutex::new(None),
+        }
+    }
+
+    /// Sleeps until the API is expected to accept requests again.
+    pub async fn wait(&self) {
+        let resume_at = *self.resume_at.lock().unwrap();
+        if let Some(resume_at) = resume_at {
+            tokio::time::sleep_until(resume_at.into()).await;
+        }
+    }
+
+    /// Records the rate limit headers of a response, pausing further requests once the current
+    /// window is used up.
+    pub fn observe(&self, headers: &HeaderMap) {
+        if header_u64(headers, RATELIMIT_REMAINING) == Some(0) {
+            let delay = reset_delay(headers).unwrap_or(WINDOW);
+            self.pause(delay);
+        }
+    }
+
+    pub fn should_retry(&self, status: StatusCode, attempt: u32) -> bool {
+        attempt < self.max_retries
+            && (status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error())
+    }
+
+    /// How long to wait before retrying a failed request, preferring what the server asked for
+    /// over exponential backoff. No wait is longer than the rate limit window, as a longer
+    /// `Retry-After` would only stall the export without a word.
+    pub fn retry_delay(&self, headers: &HeaderMap, attempt: u32) -> Duration {
+        let delay = header_u64(headers, RETRY_AFTER.as_str())
+            .map(Duration::from_secs)
+            .or_else(|| reset_delay(headers))
+            .unwrap_or_else(|| self.backoff(attempt))
+            .min(WINDOW);
+        self.pause(delay);
+        delay
+    }
+
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        (self.base_delay * 2u32.saturating_pow(attempt)).min(WINDOW)
+    }
+
+    fn pause(&self, delay: Duration) {
+        let Some(until) = Instant::now().checked_add(delay) else {
+            return;
+        };
+        let mut resume_at = self.resume_at.lock().unwrap();
+        *resume_at = Some(resume_at.map_or(until, |current| current.max(until)));
+    }
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+// X-RateLimit-Reset is a unix timestamp in seconds
+fn reset_delay(headers: &HeaderMap) -> Option<Duration> {
+    let reset = header_u64(headers, RATELIMIT_RESET)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Duration::from_secs(reset.saturating_sub(now)).min(WINDOW))
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::de::{self, value::MapAccessDeserializer};
+use serde::{Deserialize, Deserializer};
+
+use crate::error::Error;
+use crate::model::ListSelection;
+
+/// One user of a roster, along with the options overriding the command line ones for them.
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RosterEntry {
+    pub user: String,
+    pub list: Option<ListSelection>,
+    pub nsfw: Option<bool>,
+    pub update: Option<bool>,
+}
+
+impl RosterEntry {
+    pub fn new(user: &str) -> RosterEntry {
+        RosterEntry {
+            user: user.to_string(),
+            list: None,
+            nsfw: None,
+            update: None,
+        }
+    }
+}
+
+/// A user is either just a name or a table with options
+enum RawEntry {
+    Name(String),
+    Entry(RosterEntry),
+}
+
+// an untagged enum would replace the error of a table with an unknown key or a bad value by
+// "data did not match any variant", so the table's own error is passed on instead
+impl<'de> Deserialize<'de> for RawEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RawEntry, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = RawEntry;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a username or a table with a user")
+            }
+
+            fn visit_str<E: de::Error>(self, user: &str) -> Result<RawEntry, E> {
+                Ok(RawEntry::Name(user.to_string()))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<RawEntry, A::Error> {
+                RosterEntry::deserialize(MapAccessDeserializer::new(map)).map(RawEntry::Entry)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct Roster {
+    users: Vec<RawEntry>,
+}
+
+/// Loads the users to export from a roster file.
+///
+/// `.toml` and `.json` files hold a `users` array whose items are either usernames or tables with
+/// a `user` and the optional `list`, `nsfw` and `update` keys. Any other file is read as plain
+/// text, one user per line followed by any of `anime`, `manga`, `all`, `no-nsfw` and
+/// `no-update`, with `#` starting a comment.
+pub fn load(path: &Path) -> Result<Vec<RosterEntry>, Error> {
+    let input = fs::read_to_string(path)
+        .map_err(|e| Error::Input(format!("couldn't read {}: {}", path.display(), e)))?;
+
+    let roster: Roster = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(&input)
+            .map_err(|e| Error::Input(format!("{} isn't a valid roster: {}", path.display(), e)))?,
+        Some("json") => serde_json::from_str(&input)
+            .map_err(|e| Error::Input(format!("{} isn't a valid roster: {}", path.display(), e)))?,
+        _ => return parse_text(&input, path),
+    };
+    let entries: Vec<RosterEntry> = roster
+        .users
+        .into_iter()
+        .map(|entry| match entry {
+            RawEntry::Name(user) => RosterEntry::new(&user),
+            RawEntry::Entry(entry) => entry,
+        })
+        .collect();
+    if entries.is_empty() {
+        return Err(Error::Input(format!("{} lists no users", path.display())));
+    }
+    Ok(entries)
+}
+
+fn parse_text(input: &str, path: &Path) -> Result<Vec<RosterEntry>, Error> {
+    let mut entries = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let Some(user) = words.next() else {
+            continue;
+        };
+        let mut entry = RosterEntry::new(user);
+        for word in words {
+            match word {
+                "anime" => entry.list = Some(ListSelection::Anime),
+                "manga" => entry.list = Some(ListSelection::Manga),
+                "all" => entry.list = Some(ListSelection::All),
+                "no-nsfw" => entry.nsfw = Some(false),
+                "no-update" => entry.update = Some(false),
+                _ => {
+                    return Err(Error::Input(format!(
+                        "{} line {}: unknown option {}",
+                        path.display(),
+                        number + 1,
+                        word
+                    )))
+                }
+            }
+        }
+        entries.push(entry);
+    }
+    if entries.is_empty() {
+        return Err(Error::Input(format!("{} lists no users", path.display())));
+    }
+    Ok(entries)
+}
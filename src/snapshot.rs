@@ -0,0 +1,105 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::{parse_lists, parse_user};
+use crate::error::Error;
+use crate::model::{Collection, ListType, MediaEntry, MediaListGroup, User};
+
+/// The responses to the queries of one export, saved as AniList sent them.
+///
+/// Exports can be rendered from a snapshot as many times as needed, with any flags or format,
+/// without querying the API again.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    #[serde(rename = "type")]
+    pub list_type: ListType,
+    /// the `data` field of every response, the User query first followed by the list chunks
+    pub responses: Vec<serde_json::Value>,
+}
+
+impl Snapshot {
+    pub fn collection(&self) -> Result<Collection, Error> {
+        let user = self
+            .responses
+            .iter()
+            .find(|result| result.get("User").is_some())
+            .ok_or(Error::MissingData("the User query"))?;
+        let mut lists: Vec<MediaListGroup> = Vec::new();
+        for result in &self.responses {
+            if result.get("MediaListCollection").is_some() {
+                lists.extend(parse_lists(result)?);
+            }
+        }
+
+        Ok(Collection {
+            list_type: self.list_type,
+            user: parse_user(user, self.list_type)?,
+            lists,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let write = || -> io::Result<()> {
+            let mut f = BufWriter::new(File::create(path)?);
+            serde_json::to_writer_pretty(&mut f, self)?;
+            f.write_all(b"\n")?;
+            f.flush()
+        };
+        write().map_err(|e| {
+            Error::Io(io::Error::new(
+                e.kind(),
+                format!("{}: {}", path.display(), e),
+            ))
+        })
+    }
+}
+
+/// What `--format json` writes, read back in
+#[derive(Deserialize)]
+struct JsonExport {
+    #[serde(rename = "type")]
+    list_type: ListType,
+    user: User,
+    entries: Vec<MediaEntry>,
+}
+
+/// Loads a collection from either a snapshot or a JSON export.
+pub fn load(path: &Path) -> Result<Collection, Error> {
+    let input = fs::read_to_string(path)
+        .map_err(|e| Error::Input(format!("couldn't read {}: {}", path.display(), e)))?;
+    let value: serde_json::Value = serde_json::from_str(&input)
+        .map_err(|e| Error::Input(format!("{} isn't valid JSON: {}", path.display(), e)))?;
+
+    if value.get("responses").is_some() {
+        let snapshot: Snapshot = serde_json::from_value(value).map_err(|e| {
+            Error::Input(format!("{} isn't a valid snapshot: {}", path.display(), e))
+        })?;
+        snapshot.collection()
+    } else if value.get("entries").is_some() {
+        let export: JsonExport = serde_json::from_value(value).map_err(|e| {
+            Error::Input(format!(
+                "{} isn't a valid JSON export: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        // the export already holds exactly the exported entries, so they are treated as a
+        // single status list
+        Ok(Collection {
+            list_type: export.list_type,
+            user: export.user,
+            lists: vec![MediaListGroup {
+                entries: export.entries,
+                isCustomList: false,
+            }],
+        })
+    } else {
+        Err(Error::Input(format!(
+            "{} is neither a snapshot saved with --save-raw nor a JSON export",
+            path.display()
+        )))
+    }
+}
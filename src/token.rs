@@ -0,0 +1,138 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::config_dir;
+use crate::error::Error;
+use crate::oauth::Token;
+
+/// AniList access tokens are valid for a year
+const TOKEN_LIFETIME: Duration = Duration::days(365);
+
+/// An access token saved by `login` for later runs.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StoredToken {
+    pub access_token: String,
+    /// unix timestamp after which AniList no longer accepts the token
+    pub expires_at: i64,
+}
+
+impl StoredToken {
+    /// Tokens pasted from the implicit grant don't say when they expire, so they are assumed to
+    /// have the full lifetime AniList gives every token.
+    pub fn new(token: &Token) -> StoredToken {
+        let lifetime = token
+            .expires_in
+            .and_then(|secs| Duration::try_seconds(secs as i64))
+            .unwrap_or(TOKEN_LIFETIME);
+        StoredToken {
+            access_token: token.access_token.clone(),
+            expires_at: (Utc::now() + lifetime).timestamp(),
+        }
+    }
+
+    pub fn expires(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.expires_at, 0).unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires() <= Utc::now()
+    }
+
+    /// Reads the token saved at `path`, if any.
+    pub fn load(path: &Path) -> Result<Option<StoredToken>, Error> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(Error::OAuth(format!(
+                    "couldn't read {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        serde_json::from_str(&json).map(Some).map_err(|e| {
+            Error::OAuth(format!(
+                "{} isn't a valid token file: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Saves the token to `path`, readable by the current user only.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        self.write(path).map_err(|e| {
+            Error::OAuth(format!(
+                "couldn't save the token to {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            // the mode only applies to new files
+            if path.exists() {
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+            }
+        }
+        let mut f = options.open(path)?;
+        serde_json::to_writer_pretty(&mut f, self)?;
+        f.write_all(b"\n")
+    }
+
+    /// Deletes the token saved at `path`, returns whether there was one.
+    pub fn remove(path: &Path) -> Result<bool, Error> {
+        match fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(Error::OAuth(format!(
+                "couldn't delete the token at {}: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+}
+
+/// Reads the token out of a file holding either just the token or what `login` stores.
+pub fn read_token_file(path: &Path) -> Result<String, Error> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| Error::OAuth(format!("couldn't read {}: {}", path.display(), e)))?;
+    let token = match serde_json::from_str::<StoredToken>(&contents) {
+        Ok(stored) if stored.is_expired() => {
+            return Err(Error::OAuth(format!(
+                "the token in {} expired on {}",
+                path.display(),
+                stored.expires().format("%Y-%m-%d")
+            )))
+        }
+        Ok(stored) => stored.access_token,
+        Err(_) => contents.trim().to_string(),
+    };
+    let token = token.strip_prefix("Bearer ").unwrap_or(&token).trim();
+    if token.is_empty() {
+        return Err(Error::OAuth(format!("{} holds no token", path.display())));
+    }
+    Ok(token.to_string())
+}
+
+/// Where `login` saves the token.
+pub fn token_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("token.json"))
+}
@@ -0,0 +1,54 @@
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::model::{Collection, MediaEntry};
+
+/// A backend turning a collection into a document of some format.
+///
+/// The exporter calls `header` once, `entry` for every entry that passed its filters, then
+/// `footer` once, so backends never have to deal with fetching or filtering.
+pub trait OutputWriter {
+    fn header(&mut self, f: &mut dyn Write, collection: &Collection) -> io::Result<()>;
+    fn entry(&mut self, f: &mut dyn Write, entry: &MediaEntry) -> io::Result<()>;
+    fn footer(&mut self, f: &mut dyn Write) -> io::Result<()>;
+
+    /// The entries whose text had to be changed to fit the format, known once they are written.
+    fn modified(&self) -> &[ModifiedEntry] {
+        &[]
+    }
+}
+
+/// An entry written with some of its text changed, for the caller to warn about.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ModifiedEntry {
+    pub id: u64,
+    /// the title as it was written
+    pub title: String,
+    /// the fields that were changed, such as `title` or `notes`
+    pub fields: Vec<&'static str>,
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Default, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// MAL style XML, importable on MAL and AniDB
+    #[default]
+    Xml,
+    /// one row per entry, for spreadsheets and data analysis
+    Csv,
+    /// every field AniList returns, for lossless backups
+    Json,
+}
+
+impl OutputFormat {
+    /// The file extension of documents in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Xml => "xml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+        }
+    }
+}
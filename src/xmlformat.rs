@@ -1,150 +1,316 @@
+use std::fmt;
+use std::io::{self, Write};
+
 use chrono::{Datelike, Local};
-use serde::Deserialize;
 
-#[derive(Deserialize, PartialEq, Copy, Clone)]
-pub enum Status {
-    CURRENT,
-    PLANNING,
-    COMPLETED,
-    DROPPED,
-    PAUSED,
-    REPEATING,
-}
-#[derive(Deserialize, Clone)]
-#[allow(non_camel_case_types)]
-enum Format {
-    TV,
-    TV_SHORT,
-    MOVIE,
-    SPECIAL,
-    OVA,
-    ONA,
-    MUSIC,
-    MANGA,
-    NOVEL,
-    ONE_SHOT,
-    UNKNOWN, // reserved because of Rust
+use crate::model::{Collection, Date, Format, ListType, MediaEntry, Status, User};
+use crate::writer::{ModifiedEntry, OutputWriter};
+use crate::xmlstream::{is_xml_char, XmlStream};
+
+/// Writes MAL style XML, as described in mal-standard.md
+pub struct XmlWriter {
+    list_type: ListType,
+    update: bool,
+    cdata: bool,
+    xml: XmlStream,
+    /// entries that lost characters XML doesn't allow
+    modified: Vec<ModifiedEntry>,
 }
 
-#[derive(Deserialize, Clone)]
-struct Date {
-    year: Option<u32>,
-    month: Option<u8>,
-    day: Option<u8>,
-}
-#[derive(Deserialize, Clone, PartialEq)]
-pub struct Title {
-    romaji: String,
-}
-#[derive(Deserialize, Clone)]
-#[allow(non_snake_case)]
-pub struct Media {
-    idMal: Option<u64>,
-    pub isAdult: bool,
-    pub title: Title,
-    format: Option<Format>,
-    episodes: Option<u64>,
-    chapters: Option<u64>,
-    volumes: Option<u64>,
-}
-#[derive(Deserialize, Copy, Clone)]
-struct StatusEntry {
-    status: Status,
-    count: u64,
-}
+impl XmlWriter {
+    pub fn new(list_type: ListType, update: bool) -> XmlWriter {
+        XmlWriter {
+            list_type,
+            update,
+            cdata: false,
+            xml: XmlStream::new(),
+            modified: Vec::new(),
+        }
+    }
 
-#[derive(Deserialize, Clone)]
-#[allow(non_snake_case)]
-pub struct MediaEntry {
-    status: Status,
-    repeat: u64,
-    progress: u64,
-    progressVolumes: Option<u64>,
-    customLists: serde_json::Value,
-    pub hiddenFromStatusLists: bool,
-    startedAt: Date,
-    completedAt: Date,
-    score: f32,
-    notes: Option<String>,
-    pub media: Media,
-}
+    /// Wraps titles, comments and tags in CDATA sections the way MAL's own exports do, instead of
+    /// escaping them.
+    pub fn with_cdata(mut self, cdata: bool) -> XmlWriter {
+        self.cdata = cdata;
+        self
+    }
 
-#[derive(Deserialize)]
-#[allow(non_snake_case)]
-pub struct MediaListGroup {
-    pub entries: Vec<MediaEntry>,
-    pub isCustomList: bool,
-}
+    /// An element holding free text, which MAL wraps in CDATA
+    fn text(&mut self, f: &mut dyn Write, tag: &str, text: &str) -> io::Result<()> {
+        if self.cdata {
+            self.xml.cdata_element(f, tag, text)
+        } else {
+            self.xml.element(f, tag, text)
+        }
+    }
+
+    fn anime_header(&mut self, f: &mut dyn Write, user: &User) -> io::Result<()> {
+        let stats = &user.statistics;
+        let xml = &mut self.xml;
+        xml.element(f, "user_id", user.id)?;
+        xml.element(f, "user_name", &user.name)?;
+        xml.element(f, "user_export_type", 1)?;
+        xml.element(f, "user_total_anime", stats.count)?;
+        xml.element(
+            f,
+            "user_total_watching",
+            stats.count_of(Status::CURRENT) + stats.count_of(Status::REPEATING),
+        )?;
+        xml.element(f, "user_total_completed", stats.count_of(Status::COMPLETED))?;
+        xml.element(f, "user_total_onhold", stats.count_of(Status::PAUSED))?;
+        xml.element(f, "user_total_dropped", stats.count_of(Status::DROPPED))?;
+        xml.element(
+            f,
+            "user_total_plantowatch",
+            stats.count_of(Status::PLANNING),
+        )
+    }
+
+    fn manga_header(&mut self, f: &mut dyn Write, user: &User) -> io::Result<()> {
+        // NOTE: AniList only seems to report Planning, Completed, Dropped, and Paused statuses.
+        // Reading and rereading are left here for compatibility
+        let stats = &user.statistics;
+        let xml = &mut self.xml;
+        xml.element(f, "user_id", user.id)?;
+        xml.element(f, "user_name", &user.name)?;
+        xml.element(f, "user_export_type", 2)?;
+        xml.element(f, "user_total_manga", stats.count)?;
+        xml.element(
+            f,
+            "user_total_reading",
+            stats.count_of(Status::CURRENT) + stats.count_of(Status::REPEATING),
+        )?;
+        xml.element(f, "user_total_completed", stats.count_of(Status::COMPLETED))?;
+        xml.element(f, "user_total_onhold", stats.count_of(Status::PAUSED))?;
+        xml.element(f, "user_total_dropped", stats.count_of(Status::DROPPED))?;
+        xml.element(f, "user_total_plantoread", stats.count_of(Status::PLANNING))
+    }
+
+    fn anime(&mut self, f: &mut dyn Write, entry: &MediaEntry, tags: &str) -> io::Result<()> {
+        let media = &entry.media;
+        self.xml.start(f, "anime")?;
+        self.xml
+            .element(f, "series_animedb_id", media.idMal.unwrap_or(0))?;
+        self.text(f, "series_title", &media.title.romaji)?;
+        self.xml.element(
+            f,
+            "series_type",
+            media.format.as_ref().unwrap_or(&Format::UNKNOWN),
+        )?;
+        self.xml
+            .element(f, "series_episodes", media.episodes.unwrap_or(0))?;
+        self.xml.element(f, "my_id", 0)?; // keep it zero
+        self.xml.element(f, "my_watched_episodes", entry.progress)?;
+        self.xml.element(f, "my_start_date", &entry.startedAt)?;
+        self.xml.element(f, "my_finish_date", &entry.completedAt)?;
+        self.xml.element(f, "my_rated", "")?;
+        self.xml.element(f, "my_score", entry.score)?;
+        self.xml.element(f, "my_dvd", "")?;
+        self.xml.element(f, "my_storage", "")?;
+        self.xml.element(f, "my_status", entry.status)?;
+        self.text(f, "my_comments", entry.notes.as_deref().unwrap_or_default())?;
+        self.xml.element(f, "my_times_watched", entry.repeat)?;
+        self.xml.element(f, "my_rewatch_value", "")?; // MAL only
+        self.xml.element(f, "my_priority", "")?;
+        self.text(f, "my_tags", tags)?;
+        if entry.status == Status::REPEATING {
+            self.xml.element(f, "my_rewatching", 1)?;
+            self.xml.element(f, "my_rewatchin_ep", entry.progress)?;
+        } else {
+            self.xml.element(f, "my_rewatching", 0)?;
+            self.xml.element(f, "my_rewatching_ep", 0)?;
+        }
+        self.xml.element(f, "my_discuss", 1)?;
+        self.xml.element(f, "my_sns", "default")?;
+        self.xml
+            .element(f, "update_on_import", u8::from(self.update))?;
+        self.xml.end(f)
+    }
 
-#[derive(Deserialize)]
-#[allow(non_snake_case)]
-pub struct UserStatistics {
-    count: u64,
-    statuses: Vec<StatusEntry>,
+    fn manga(&mut self, f: &mut dyn Write, entry: &MediaEntry, tags: &str) -> io::Result<()> {
+        let media = &entry.media;
+        self.xml.start(f, "manga")?;
+        self.xml
+            .element(f, "manga_mangadb_id", media.idMal.unwrap_or(0))?;
+        self.text(f, "manga_title", &media.title.romaji)?;
+        self.xml
+            .element(f, "manga_volumes", media.volumes.unwrap_or(0))?;
+        self.xml
+            .element(f, "manga_chapters", media.chapters.unwrap_or(0))?;
+        self.xml.element(f, "my_id", 0)?; // keep it zero
+        self.xml
+            .element(f, "my_read_volumes", entry.progressVolumes.unwrap_or(0))?;
+        self.xml.element(f, "my_read_chapters", entry.progress)?;
+        self.xml.element(f, "my_start_date", &entry.startedAt)?;
+        self.xml.element(f, "my_finish_date", &entry.completedAt)?;
+        self.xml.element(f, "my_scanalation_group", "")?;
+        self.xml.element(f, "my_score", entry.score)?;
+        self.xml.element(f, "my_storage", "")?;
+        self.xml.element(f, "my_retail_volumes", 0)?;
+        self.xml
+            .element(f, "my_status", manga_status(entry.status))?;
+        self.text(f, "my_comments", entry.notes.as_deref().unwrap_or_default())?;
+        self.xml.element(f, "my_times_read", entry.repeat)?;
+        self.text(f, "my_tags", tags)?;
+        self.xml.element(f, "my_priority", "")?;
+        self.xml.element(f, "my_reread_value", "")?; // MAL only
+        self.xml.element(
+            f,
+            "my_rereading",
+            if entry.status == Status::REPEATING {
+                "YES"
+            } else {
+                "NO"
+            },
+        )?;
+        self.xml.element(f, "my_discuss", "YES")?;
+        self.xml.element(f, "my_sns", "default")?;
+        self.xml
+            .element(f, "update_on_import", u8::from(self.update))?;
+        self.xml.end(f)
+    }
 }
 
-impl std::string::ToString for Status {
-    fn to_string(&self) -> String {
-        match self {
-            Status::CURRENT => String::from("Watching"),
-            Status::PLANNING => String::from("Plan to Watch"),
-            Status::COMPLETED => String::from("Completed"),
-            Status::DROPPED => String::from("Dropped"),
-            Status::PAUSED => String::from("On-Hold"),
-            Status::REPEATING => String::from("Completed"),
+impl OutputWriter for XmlWriter {
+    fn header(&mut self, f: &mut dyn Write, collection: &Collection) -> io::Result<()> {
+        let user = &collection.user;
+        let now = Local::now();
+        self.xml.declaration(f)?;
+        self.xml.start(f, "myanimelist")?;
+        self.xml.comment(
+            f,
+            format_args!(
+                "Export done by {} v{} by {} on {}-{:02}-{:02} for the AniList account named {}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION"),
+                env!("CARGO_PKG_AUTHORS"),
+                now.year(),
+                now.month(),
+                now.day(),
+                user.name
+            ),
+        )?;
+        self.xml.start(f, "myinfo")?;
+        match self.list_type {
+            ListType::Anime => self.anime_header(f, user)?,
+            ListType::Manga => self.manga_header(f, user)?,
+        }
+        self.xml.end(f)
+    }
+
+    fn entry(&mut self, f: &mut dyn Write, entry: &MediaEntry) -> io::Result<()> {
+        let tags = lists_to_tags(&entry.customLists);
+        let fields: Vec<&'static str> = [
+            ("title", entry.media.title.romaji.as_str()),
+            ("notes", entry.notes.as_deref().unwrap_or_default()),
+            ("tags", tags.as_str()),
+        ]
+        .into_iter()
+        .filter(|(_, text)| !text.chars().all(is_xml_char))
+        .map(|(field, _)| field)
+        .collect();
+        if !fields.is_empty() {
+            self.modified.push(ModifiedEntry {
+                id: entry.media.id,
+                title: entry
+                    .media
+                    .title
+                    .romaji
+                    .chars()
+                    .filter(|c| is_xml_char(*c))
+                    .collect(),
+                fields,
+            });
+        }
+
+        // entries MAL doesn't know can't be imported, but are kept in the file commented out
+        let unknown = entry.media.idMal.is_none();
+        if unknown {
+            self.xml.start_comment(f)?;
+        }
+        match self.list_type {
+            ListType::Anime => self.anime(f, entry, &tags)?,
+            ListType::Manga => self.manga(f, entry, &tags)?,
+        }
+        if unknown {
+            self.xml.end_comment(f)?;
         }
+        Ok(())
+    }
+
+    fn footer(&mut self, f: &mut dyn Write) -> io::Result<()> {
+        self.xml.end(f)
+    }
+
+    fn modified(&self) -> &[ModifiedEntry] {
+        &self.modified
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::CURRENT => "Watching",
+            Status::PLANNING => "Plan to Watch",
+            Status::COMPLETED => "Completed",
+            Status::DROPPED => "Dropped",
+            Status::PAUSED => "On-Hold",
+            Status::REPEATING => "Completed",
+        })
     }
 }
 
-fn to_string_manga(status: Status) -> String {
+fn manga_status(status: Status) -> &'static str {
     match status {
-        Status::CURRENT => String::from("Reading"),
-        Status::PLANNING => String::from("Plan to Read"),
-        _ => status.to_string(),
+        Status::CURRENT => "Reading",
+        Status::PLANNING => "Plan to Read",
+        Status::COMPLETED | Status::REPEATING => "Completed",
+        Status::DROPPED => "Dropped",
+        Status::PAUSED => "On-Hold",
     }
 }
 
-impl std::string::ToString for Format {
-    fn to_string(&self) -> String {
-        match self {
-            Format::TV => String::from("TV"),
-            Format::TV_SHORT => String::from("TV_SHORT"),
-            Format::MOVIE => String::from("MOVIE"),
-            Format::SPECIAL => String::from("SPECIAL"),
-            Format::OVA => String::from("OVA"),
-            Format::ONA => String::from("ONA"),
-            Format::MUSIC => String::from("MUSIC"),
-            Format::MANGA => String::from("MANGA"),
-            Format::NOVEL => String::from("NOVEL"),
-            Format::ONE_SHOT => String::from("ONE_SHOT"),
-            Format::UNKNOWN => String::from("Unknown"),
-        }
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Format::TV => "TV",
+            Format::TV_SHORT => "TV_SHORT",
+            Format::MOVIE => "MOVIE",
+            Format::SPECIAL => "SPECIAL",
+            Format::OVA => "OVA",
+            Format::ONA => "ONA",
+            Format::MUSIC => "MUSIC",
+            Format::MANGA => "MANGA",
+            Format::NOVEL => "NOVEL",
+            Format::ONE_SHOT => "ONE_SHOT",
+            Format::UNKNOWN => "Unknown",
+        })
     }
 }
 
-impl std::string::ToString for Date {
-    fn to_string(&self) -> String {
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut buffer: String = String::new();
         match self.year {
             Some(i) => buffer.push_str(&format!("{:04}", i)),
             None => buffer.push_str("0000"),
         };
-        buffer.push_str("-");
+        buffer.push('-');
         match self.month {
             Some(i) => buffer.push_str(&format!("{:02}", i)),
             None => buffer.push_str("00"),
         };
-        buffer.push_str("-");
+        buffer.push('-');
         match self.day {
             Some(i) => buffer.push_str(&format!("{:02}", i)),
             None => buffer.push_str("00"),
         };
 
-        buffer
+        f.write_str(&buffer)
     }
 }
 
-fn lists_to_tags(custom_lists: serde_json::Value) -> String {
+pub(crate) fn lists_to_tags(custom_lists: &serde_json::Value) -> String {
     match custom_lists.as_object() {
         Some(map) => map
             .iter()
@@ -160,390 +326,3 @@ fn lists_to_tags(custom_lists: serde_json::Value) -> String {
         None => String::new(),
     }
 }
-
-fn xml_str(input: &str) -> String {
-    // sanitize XML strings
-    // as per https://www.w3resource.com/xml/prohibited-character-literals.php
-    let mut out = str::replace(input, "&", "&amp;");
-    out = str::replace(&out, "<", "&lt;");
-    out = str::replace(&out, ">", "&gt;");
-    out = str::replace(&out, "'", "&apos;");
-    out = str::replace(&out, "\"", "&quot;");
-
-    out
-}
-
-fn xml_tag(indent: Option<i8>, tag: &str, contents: &str) -> String {
-    let mut xml: String = String::new();
-    xml.extend((0..(indent.unwrap_or(0))).map(|_| String::from("\t"))); // sorry
-
-    xml.push_str("<");
-    xml.push_str(tag);
-    xml.push_str(">");
-
-    // possible optimization to consider
-    // for this application, only one type of tag will need to be escaped
-    xml.push_str(&xml_str(contents));
-
-    xml.push_str("</");
-    xml.push_str(tag);
-    xml.push_str(">\n");
-    xml
-}
-
-fn string_option_unwrap(string: Option<String>) -> String {
-    match string {
-        None => String::new(),
-        Some(content) => content,
-    }
-}
-
-pub fn xml_animeheader(stats: UserStatistics, id: u64, name: String) -> String {
-    let default_status: StatusEntry = StatusEntry {
-        status: Status::CURRENT, // ignored
-        count: 0,
-    };
-    let default_status_ref = &&default_status;
-
-    let closure = |entry: &Vec<StatusEntry>, status: Status| {
-        **entry
-            .into_iter()
-            .filter(|s| s.status == status)
-            .collect::<Vec<_>>()
-            .get(0)
-            .unwrap_or(default_status_ref)
-    };
-    let status_watching: StatusEntry = closure(&stats.statuses, Status::CURRENT);
-    let status_rewatching: StatusEntry = closure(&stats.statuses, Status::REPEATING);
-    let status_completed: StatusEntry = closure(&stats.statuses, Status::COMPLETED);
-    let status_onhold: StatusEntry = closure(&stats.statuses, Status::PAUSED);
-    let status_dropped: StatusEntry = closure(&stats.statuses, Status::DROPPED);
-    let status_plantowatch: StatusEntry = closure(&stats.statuses, Status::PLANNING);
-
-    let mut header: String = String::new();
-    header.push_str(&xml_tag(Some(2), "user_id", &id.to_string()));
-    header.push_str(&xml_tag(Some(2), "user_name", &name));
-    header.push_str(&xml_tag(Some(2), "user_export_type", "1"));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_anime",
-        &stats.count.to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_watching",
-        &(status_watching.count + status_rewatching.count).to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_completed",
-        &status_completed.count.to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_onhold",
-        &status_onhold.count.to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_dropped",
-        &status_dropped.count.to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_plantowatch",
-        &status_plantowatch.count.to_string(),
-    ));
-
-    header
-}
-
-pub fn xml_mangaheader(stats: UserStatistics, id: u64, name: String) -> String {
-    let default_status: StatusEntry = StatusEntry {
-        status: Status::CURRENT, // ignored
-        count: 0,
-    };
-    let default_status_ref = &&default_status;
-
-    let closure = |entry: &Vec<StatusEntry>, status: Status| {
-        **entry
-            .into_iter()
-            .filter(|s| s.status == status)
-            .collect::<Vec<_>>()
-            .get(0)
-            .unwrap_or(default_status_ref)
-    };
-
-    // NOTE: AniList only seems to report Planning, Completed, Dropped, and Paused statuses. This
-    // is left here for compatibility
-    let status_reading: StatusEntry = closure(&stats.statuses, Status::CURRENT);
-    let status_rereading: StatusEntry = closure(&stats.statuses, Status::REPEATING);
-    let status_completed: StatusEntry = closure(&stats.statuses, Status::COMPLETED);
-    let status_onhold: StatusEntry = closure(&stats.statuses, Status::PAUSED);
-    let status_dropped: StatusEntry = closure(&stats.statuses, Status::DROPPED);
-    let status_plantowatch: StatusEntry = closure(&stats.statuses, Status::PLANNING);
-
-    let mut header: String = String::new();
-    header.push_str(&xml_tag(Some(2), "user_id", &id.to_string()));
-    header.push_str(&xml_tag(Some(2), "user_name", &name));
-    header.push_str(&xml_tag(Some(2), "user_export_type", "2"));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_manga",
-        &stats.count.to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_reading",
-        &(status_reading.count + status_rereading.count).to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_completed",
-        &status_completed.count.to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_onhold",
-        &status_onhold.count.to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_dropped",
-        &status_dropped.count.to_string(),
-    ));
-    header.push_str(&xml_tag(
-        Some(2),
-        "user_total_plantoread",
-        &status_plantowatch.count.to_string(),
-    ));
-
-    header
-}
-
-pub fn xml_export_comment(username: &str) -> String {
-    let mut buffer = String::new();
-    let now = Local::now();
-
-    buffer.push_str("<!--\n");
-    buffer.push_str("Export done by ");
-    buffer.push_str(env!("CARGO_PKG_NAME"));
-    buffer.push_str(" v");
-    buffer.push_str(env!("CARGO_PKG_VERSION"));
-    buffer.push_str(" by ");
-    buffer.push_str(env!("CARGO_PKG_AUTHORS"));
-    buffer.push_str(&format!(
-        " on {}-{:02}-{:02}",
-        now.year(),
-        now.month(),
-        now.day(),
-    ));
-    buffer.push_str(" for the AniList account named ");
-    buffer.push_str(username);
-    buffer.push_str("\n-->");
-
-    buffer
-}
-
-pub fn xml_anime(anime_entry: MediaEntry, update: bool) -> String {
-    let mut xmlout: String = String::new();
-    match anime_entry.media.idMal {
-        None => xmlout.push_str("<!--\n"),
-        Some(_) => {}
-    }
-    xmlout.push_str("\t<anime>\n");
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "series_animedb_id",
-        &anime_entry.media.idMal.unwrap_or(0).to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "series_title",
-        &anime_entry.media.title.romaji,
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "series_type",
-        &anime_entry
-            .media
-            .format
-            .unwrap_or(Format::UNKNOWN)
-            .to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "series_episodes",
-        &anime_entry.media.episodes.unwrap_or(0).to_string(),
-    ));
-    xmlout.push_str(&xml_tag(Some(2), "my_id", "0")); // keep it zero
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_watched_episodes",
-        &anime_entry.progress.to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_start_date",
-        &anime_entry.startedAt.to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_finish_date",
-        &anime_entry.completedAt.to_string(),
-    ));
-    xmlout.push_str(&xml_tag(Some(2), "my_rated", ""));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_score",
-        &anime_entry.score.to_string(),
-    ));
-    xmlout.push_str(&xml_tag(Some(2), "my_dvd", ""));
-    xmlout.push_str(&xml_tag(Some(2), "my_storage", ""));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_status",
-        &anime_entry.status.to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_comments",
-        &string_option_unwrap(anime_entry.notes),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_times_watched",
-        &(anime_entry.repeat).to_string(),
-    ));
-    xmlout.push_str(&xml_tag(Some(2), "my_rewatch_value", "")); // MAL only
-    xmlout.push_str(&xml_tag(Some(2), "my_priority", ""));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_tags",
-        &lists_to_tags(anime_entry.customLists),
-    ));
-    if anime_entry.status == Status::REPEATING {
-        xmlout.push_str(&xml_tag(Some(2), "my_rewatching", "1"));
-        xmlout.push_str(&xml_tag(
-            Some(2),
-            "my_rewatchin_ep",
-            &anime_entry.progress.to_string(),
-        ));
-    } else {
-        xmlout.push_str(&xml_tag(Some(2), "my_rewatching", "0"));
-        xmlout.push_str(&xml_tag(Some(2), "my_rewatching_ep", "0"));
-    }
-    xmlout.push_str(&xml_tag(Some(2), "my_discuss", "1"));
-    xmlout.push_str(&xml_tag(Some(2), "my_sns", "default"));
-    if update {
-        xmlout.push_str(&xml_tag(Some(2), "update_on_import", "1"));
-    } else {
-        xmlout.push_str(&xml_tag(Some(2), "update_on_import", "0"));
-    }
-    xmlout.push_str("\t</anime>");
-    match anime_entry.media.idMal {
-        None => xmlout.push_str("\n-->\n"),
-        Some(_) => {}
-    }
-
-    xmlout
-}
-
-pub fn xml_manga(manga_entry: MediaEntry, update: bool) -> String {
-    let mut xmlout: String = String::new();
-    match manga_entry.media.idMal {
-        None => xmlout.push_str("<!--\n"),
-        Some(_) => {}
-    }
-    xmlout.push_str("\t<manga>\n");
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "manga_mangadb_id",
-        &manga_entry.media.idMal.unwrap_or(0).to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "manga_title",
-        &manga_entry.media.title.romaji,
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "manga_volumes",
-        &manga_entry.media.volumes.unwrap_or(0).to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "manga_chapters",
-        &manga_entry.media.chapters.unwrap_or(0).to_string(),
-    ));
-    xmlout.push_str(&xml_tag(Some(2), "my_id", "0")); // keep it zero
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_read_volumes",
-        &manga_entry.progressVolumes.unwrap_or(0).to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_read_chapters",
-        &manga_entry.progress.to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_start_date",
-        &manga_entry.startedAt.to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_finish_date",
-        &manga_entry.completedAt.to_string(),
-    ));
-    xmlout.push_str(&xml_tag(Some(2), "my_scanalation_group", ""));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_score",
-        &manga_entry.score.to_string(),
-    ));
-    xmlout.push_str(&xml_tag(Some(2), "my_storage", ""));
-    xmlout.push_str(&xml_tag(Some(2), "my_retail_volumes", "0"));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_status",
-        &to_string_manga(manga_entry.status),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_comments",
-        &string_option_unwrap(manga_entry.notes),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_times_read",
-        &(manga_entry.repeat).to_string(),
-    ));
-    xmlout.push_str(&xml_tag(
-        Some(2),
-        "my_tags",
-        &lists_to_tags(manga_entry.customLists),
-    ));
-    xmlout.push_str(&xml_tag(Some(2), "my_priority", ""));
-    xmlout.push_str(&xml_tag(Some(2), "my_reread_value", "")); // MAL only
-    if manga_entry.status == Status::REPEATING {
-        xmlout.push_str(&xml_tag(Some(2), "my_rereading", "YES"));
-    } else {
-        xmlout.push_str(&xml_tag(Some(2), "my_rereading", "NO"));
-    }
-    xmlout.push_str(&xml_tag(Some(2), "my_discuss", "YES"));
-    xmlout.push_str(&xml_tag(Some(2), "my_sns", "default"));
-    if update {
-        xmlout.push_str(&xml_tag(Some(2), "update_on_import", "1"));
-    } else {
-        xmlout.push_str(&xml_tag(Some(2), "update_on_import", "0"));
-    }
-    xmlout.push_str("\t</manga>");
-    match manga_entry.media.idMal {
-        None => xmlout.push_str("\n-->\n"),
-        Some(_) => {}
-    }
-
-    xmlout
-}
@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
+use std::iter;
+
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesCData, BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+
+/// Writes an XML document piece by piece straight to the output, indented with tabs.
+///
+/// quick-xml writes the elements, escapes the text and splits CDATA sections, while this keeps
+/// track of the indentation and of elements commented out as a whole. Text in those is further
+/// kept from ending the comment early, while still reading back as the original text once the
+/// comment is removed.
+#[derive(Debug, Default)]
+pub struct XmlStream {
+    open: Vec<&'static str>,
+    in_comment: bool,
+}
+
+impl XmlStream {
+    pub fn new() -> XmlStream {
+        XmlStream::default()
+    }
+
+    pub fn declaration(&mut self, f: &mut dyn Write) -> io::Result<()> {
+        let decl = BytesDecl::new("1.0", Some("UTF-8"), None);
+        Writer::new(&mut *f).write_event(Event::Decl(decl))?;
+        f.write_all(b"\n")
+    }
+
+    pub fn start(&mut self, f: &mut dyn Write, tag: &'static str) -> io::Result<()> {
+        self.line(f, [Event::Start(BytesStart::new(tag))])?;
+        self.open.push(tag);
+        Ok(())
+    }
+
+    /// Closes the innermost open element.
+    pub fn end(&mut self, f: &mut dyn Write) -> io::Result<()> {
+        let tag = self
+            .open
+            .pop()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no element is open"))?;
+        self.line(f, [Event::End(BytesEnd::new(tag))])
+    }
+
+    /// Writes `<tag>value</tag>` on a line of its own.
+    pub fn element(
+        &mut self,
+        f: &mut dyn Write,
+        tag: &str,
+        value: impl fmt::Display,
+    ) -> io::Result<()> {
+        let value = value.to_string();
+        let text = BytesText::from_escaped(self.escaped(&value));
+        self.line(
+            f,
+            [
+                Event::Start(BytesStart::new(tag)),
+                Event::Text(text),
+                Event::End(BytesEnd::new(tag)),
+            ],
+        )
+    }
+
+    /// Like `element`, with the text in CDATA sections instead of escaped.
+    pub fn cdata_element(&mut self, f: &mut dyn Write, tag: &str, text: &str) -> io::Result<()> {
+        let text = strip(text);
+        // a comment can't hold "--" even inside CDATA, so the section is split between the hyphens
+        let pieces = if self.in_comment {
+            split_hyphen_pairs(&text)
+        } else {
+            vec![&text[..]]
+        };
+        let sections = pieces
+            .into_iter()
+            .flat_map(BytesCData::escaped)
+            .map(Event::CData);
+        self.line(
+            f,
+            iter::once(Event::Start(BytesStart::new(tag)))
+                .chain(sections)
+                .chain(iter::once(Event::End(BytesEnd::new(tag)))),
+        )
+    }
+
+    /// Comments out everything written until `end_comment`.
+    pub fn start_comment(&mut self, f: &mut dyn Write) -> io::Result<()> {
+        if self.in_comment {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "comments can't be nested",
+            ));
+        }
+        self.in_comment = true;
+        f.write_all(b"<!--\n")
+    }
+
+    pub fn end_comment(&mut self, f: &mut dyn Write) -> io::Result<()> {
+        if !self.in_comment {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no comment is open",
+            ));
+        }
+        self.in_comment = false;
+        f.write_all(b"-->\n")
+    }
+
+    /// Writes a comment holding `text` on lines of its own.
+    pub fn comment(&mut self, f: &mut dyn Write, text: impl fmt::Display) -> io::Result<()> {
+        let text = text.to_string();
+        let text = separate_hyphens(escape(strip(&text)));
+        let comment = BytesText::from_escaped(format!("\n{}\n", text));
+        Writer::new(&mut *f).write_event(Event::Comment(comment))?;
+        f.write_all(b"\n")
+    }
+
+    /// Writes `events` on a line of its own at the current indentation.
+    fn line<'a>(
+        &self,
+        f: &mut dyn Write,
+        events: impl IntoIterator<Item = Event<'a>>,
+    ) -> io::Result<()> {
+        for _ in 0..self.open.len() {
+            f.write_all(b"\t")?;
+        }
+        let mut writer = Writer::new(&mut *f);
+        for event in events {
+            writer.write_event(event)?;
+        }
+        f.write_all(b"\n")
+    }
+
+    /// `text` escaped, and kept from ending the comment it may be in.
+    fn escaped<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text = escape(strip(text));
+        if self.in_comment {
+            separate_hyphens(text)
+        } else {
+            text
+        }
+    }
+}
+
+/// Whether XML 1.0 allows `c` in a document at all, escaped or not. MAL rejects the whole file
+/// over a single one of the control characters it doesn't.
+pub fn is_xml_char(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..
+    )
+}
+
+fn strip(text: &str) -> Cow<'_, str> {
+    if text.chars().all(is_xml_char) {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(text.chars().filter(|c| is_xml_char(*c)).collect())
+    }
+}
+
+/// Writes the second hyphen of every pair as a character reference, as "--" can't appear in a
+/// comment.
+fn separate_hyphens(text: Cow<'_, str>) -> Cow<'_, str> {
+    if !text.contains("--") {
+        return text;
+    }
+    let mut separated = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        if c == '-' && separated.ends_with('-') {
+            separated.push_str("&#45;");
+        } else {
+            separated.push(c);
+        }
+    }
+    Cow::Owned(separated)
+}
+
+/// Splits `text` between the hyphens of every pair.
+fn split_hyphen_pairs(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (i, pair) in text.as_bytes().windows(2).enumerate() {
+        if pair == b"--" {
+            pieces.push(&text[start..=i]);
+            start = i + 1;
+        }
+    }
+    pieces.push(&text[start..]);
+    pieces
+}
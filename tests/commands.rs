@@ -0,0 +1,181 @@
+mod common;
+
+use std::fs;
+
+use common::{run, run_export, scratch_dir, MockServer};
+
+const OLD: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<myanimelist>
+  <myinfo>
+    <user_export_type>1</user_export_type>
+  </myinfo>
+  <anime>
+    <series_animedb_id>1</series_animedb_id>
+    <series_title>Cowboy Bebop</series_title>
+    <my_status>Watching</my_status>
+    <my_score>7</my_score>
+  </anime>
+  <anime>
+    <series_animedb_id>5</series_animedb_id>
+    <series_title>Cowboy Bebop: Tengoku no Tobira</series_title>
+    <my_status>Plan to Watch</my_status>
+  </anime>
+</myanimelist>
+"#;
+
+const NEW: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<myanimelist>
+  <myinfo>
+    <user_export_type>1</user_export_type>
+  </myinfo>
+  <anime>
+    <series_animedb_id>1</series_animedb_id>
+    <series_title>Cowboy Bebop</series_title>
+    <my_status>Completed</my_status>
+    <my_score>9</my_score>
+  </anime>
+  <anime>
+    <series_animedb_id>30</series_animedb_id>
+    <series_title>Neon Genesis Evangelion</series_title>
+    <my_status>Watching</my_status>
+  </anime>
+</myanimelist>
+"#;
+
+#[test]
+fn export_command_matches_running_without_one() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("export_command");
+    let plain = dir.join("plain.xml");
+    let command = dir.join("command.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            plain.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let output = run(&[
+        "export",
+        "--endpoint",
+        &server.url(),
+        "-u",
+        "mockuser",
+        "-l",
+        "anime",
+        "-f",
+        command.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+
+    assert_eq!(
+        fs::read_to_string(&plain).unwrap(),
+        fs::read_to_string(&command).unwrap()
+    );
+}
+
+#[test]
+fn stats_counts_the_entries_of_each_status() {
+    let server = MockServer::replay();
+    let output = run(&["stats", "--endpoint", &server.url(), "-u", "mockuser"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("mockuser's anime list: 5 entries\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("  completed  2\n"), "{}", stdout);
+    assert!(stdout.contains("  repeating  1\n"), "{}", stdout);
+    assert!(stdout.contains("mockuser's manga list:"), "{}", stdout);
+    // only the User queries are needed
+    assert_eq!(server.request_count(), 2);
+}
+
+#[test]
+fn validate_accepts_a_fresh_export() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("validate_export");
+    let file = dir.join("anime.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = run(&["validate", file.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Ready to import"));
+}
+
+#[test]
+fn validate_reports_every_problem() {
+    let dir = scratch_dir("validate_problems");
+    let file = dir.join("broken.xml");
+    fs::write(
+        &file,
+        NEW.replace("<my_score>9</my_score>", "<my_score>90</my_score>")
+            .replace(">30<", ">1<")
+            .replace("<my_status>Watching", "<my_status>Reading"),
+    )
+    .unwrap();
+
+    let output = run(&["validate", file.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(14), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("Cowboy Bebop (1) has the invalid score \"90\""),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Neon Genesis Evangelion (1) appears more than once"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Neon Genesis Evangelion (1) has the unknown status \"Reading\""),
+        "{}",
+        stdout
+    );
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("the export has 3 problems"));
+}
+
+#[test]
+fn diff_shows_what_changed_between_exports() {
+    let dir = scratch_dir("diff");
+    let (old, new) = (dir.join("old.xml"), dir.join("new.xml"));
+    fs::write(&old, OLD).unwrap();
+    fs::write(&new, NEW).unwrap();
+
+    let output = run(&["diff", old.to_str().unwrap(), new.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "~ [1] Cowboy Bebop\n\
+         \x20   my_score: \"7\" -> \"9\"\n\
+         \x20   my_status: \"Watching\" -> \"Completed\"\n\
+         + [30] Neon Genesis Evangelion\n\
+         - [5] Cowboy Bebop: Tengoku no Tobira\n\
+         1 added, 1 removed, 1 changed\n"
+    );
+}
@@ -0,0 +1,213 @@
+//! Minimal HTTP server standing in for the AniList GraphQL API.
+//!
+//! Every connection gets exactly one response and is then closed, which is all reqwest needs to
+//! talk to it. Requests are answered by a handler so tests can either replay the recorded
+//! responses in `tests/fixtures` or script failures.
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub struct Request {
+    pub headers: Vec<(String, String)>,
+    pub body: serde_json::Value,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn query(&self) -> &str {
+        self.body["query"].as_str().unwrap_or_default()
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, body: String) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            body,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    pub fn start<F>(handler: F) -> MockServer
+    where
+        F: Fn(&Request) -> Response + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("couldn't bind mock server");
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                serve(stream, &handler, &log);
+            }
+        });
+
+        MockServer { addr, requests }
+    }
+
+    /// Answers every query with the matching recorded response from `tests/fixtures`.
+    pub fn replay() -> MockServer {
+        MockServer::start(|request| Response::json(200, recorded(request)))
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    pub fn requests<T>(&self, f: impl FnOnce(&[Request]) -> T) -> T {
+        f(&self.requests.lock().unwrap())
+    }
+}
+
+fn serve<F>(stream: TcpStream, handler: &F, log: &Mutex<Vec<Request>>) -> Option<()>
+where
+    F: Fn(&Request) -> Response,
+{
+    let mut reader = BufReader::new(stream.try_clone().ok()?);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?; // request line
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let length: usize = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    let request = Request {
+        headers,
+        body: serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null),
+    };
+    let response = handler(&request);
+    // logged before answering so the client can never observe a response that isn't recorded yet
+    log.lock().unwrap().push(request);
+
+    let mut out = stream;
+    write!(
+        out,
+        "HTTP/1.1 {} MOCK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    )
+    .ok()?;
+    for (key, value) in &response.headers {
+        write!(out, "{}: {}\r\n", key, value).ok()?;
+    }
+    write!(out, "\r\n{}", response.body).ok()?;
+    out.flush().ok()
+}
+
+pub fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("missing fixture {:?}", path))
+}
+
+/// Picks the recorded response for a query based on what it asks for.
+pub fn recorded(request: &Request) -> String {
+    let query = request.query();
+    if query.contains("Viewer") {
+        fixture("viewer.json")
+    } else if query.contains("MediaListCollection") {
+        match request.body["variables"]["type"].as_str() {
+            Some("MANGA") => fixture("list_manga.json"),
+            _ => fixture("list_anime.json"),
+        }
+    } else if query.contains("manga {") {
+        fixture("user_manga.json")
+    } else {
+        fixture("user_anime.json")
+    }
+}
+
+/// Runs the exporter binary against `endpoint` with the given extra arguments.
+pub fn run_export(endpoint: &str, args: &[&str]) -> Output {
+    let mut all = vec!["--endpoint", endpoint];
+    all.extend(args);
+    run(&all)
+}
+
+/// Like `run_export`, with `config_home` standing in for `$XDG_CONFIG_HOME`.
+pub fn run_export_in(config_home: &Path, endpoint: &str, args: &[&str]) -> Output {
+    let mut all = vec!["--endpoint", endpoint];
+    all.extend(args);
+    run_in(config_home, &all)
+}
+
+/// Runs the exporter binary with exactly the given arguments, such as a subcommand.
+pub fn run(args: &[&str]) -> Output {
+    // keeps a token or config file of a real user out of the tests
+    run_in(
+        &Path::new(env!("CARGO_TARGET_TMPDIR")).join("no-config"),
+        args,
+    )
+}
+
+fn run_in(config_home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mal-export-for-anilist"))
+        .args(args)
+        .env_remove("ANILIST_ENDPOINT")
+        .env_remove("ANILIST_TOKEN")
+        .env("XDG_CONFIG_HOME", config_home)
+        .output()
+        .expect("couldn't run the exporter")
+}
+
+/// A scratch directory under the target dir, wiped on creation.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,176 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use common::{run_export_in, scratch_dir, MockServer};
+
+/// Writes `config` where the exporter looks for its config file, returns the config home.
+fn write_config(dir: &Path, config: &str) -> PathBuf {
+    let home = dir.join("config");
+    fs::create_dir_all(home.join("mal-export-for-anilist")).unwrap();
+    fs::write(
+        home.join("mal-export-for-anilist").join("config.toml"),
+        config,
+    )
+    .unwrap();
+    home
+}
+
+#[test]
+fn config_file_supplies_the_flags() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("config_defaults");
+    let home = write_config(
+        &dir,
+        &format!(
+            "user = \"mockuser\"\nlist = \"anime\"\nfile = \"{}\"\nformat = \"csv\"\nnsfw = false\n",
+            dir.join("{user}-{list}.csv").display()
+        ),
+    );
+
+    let output = run_export_in(&home, &server.url(), &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let csv = fs::read_to_string(dir.join("mockuser-anime.csv")).unwrap();
+    assert!(csv.starts_with("mal_id,anilist_id,title,"));
+    assert!(!csv.contains("Adult Series"));
+}
+
+#[test]
+fn flags_override_the_config_file() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("config_overrides");
+    let home = write_config(
+        &dir,
+        &format!(
+            "user = \"mockuser\"\nlist = \"anime\"\nfile = \"{}\"\nformat = \"csv\"\nupdate = false\n",
+            dir.join("{list}.csv").display()
+        ),
+    );
+
+    let output = run_export_in(
+        &home,
+        &server.url(),
+        &[
+            "-l",
+            "manga",
+            "--format",
+            "xml",
+            "-f",
+            dir.join("{list}.xml").to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let xml = fs::read_to_string(dir.join("manga.xml")).unwrap();
+    assert!(xml.contains("<manga_title>Kimetsu no Yaiba</manga_title>"));
+    assert!(xml.contains("<update_on_import>0</update_on_import>"));
+    assert!(!dir.join("anime.csv").exists());
+}
+
+#[test]
+fn config_file_can_be_given_explicitly() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("config_explicit");
+    let config = dir.join("backups.toml");
+    fs::write(
+        &config,
+        format!(
+            "user = \"mockuser\"\nlist = \"manga\"\nfile = \"{}\"\n",
+            dir.join("manga.xml").display()
+        ),
+    )
+    .unwrap();
+    let home = dir.join("empty");
+
+    let output = run_export_in(
+        &home,
+        &server.url(),
+        &["--config", config.to_str().unwrap()],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.join("manga.xml").exists());
+
+    let output = run_export_in(
+        &home,
+        &server.url(),
+        &["--config", dir.join("missing.toml").to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(13));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("missing.toml doesn't exist"), "{}", stderr);
+}
+
+#[test]
+fn flags_turn_off_switches_the_config_file_turned_on() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("config_switches");
+    let home = write_config(
+        &dir,
+        &format!(
+            "user = \"mockuser\"\nlist = \"anime\"\nfile = \"{}\"\ngzip = true\ncdata = true\nupdate = false\nnsfw = false\n",
+            dir.join("anime.xml").display()
+        ),
+    );
+
+    let output = run_export_in(
+        &home,
+        &server.url(),
+        &["--no-gzip", "--no-cdata", "--update", "--nsfw"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    // not gzipped, so it reads as text
+    let xml = fs::read_to_string(dir.join("anime.xml")).unwrap();
+    assert!(xml.contains("<series_title>Adult Series</series_title>"));
+    assert!(xml.contains("<update_on_import>1</update_on_import>"));
+    assert!(!xml.contains("CDATA"));
+
+    // the last of two opposite flags wins
+    let output = run_export_in(&home, &server.url(), &["--no-gzip", "--gzip"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(fs::read(dir.join("anime.xml"))
+        .unwrap()
+        .starts_with(&[0x1f, 0x8b]));
+}
+
+#[test]
+fn token_environment_variable_beats_the_config_file() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("config_token");
+    let home = write_config(
+        &dir,
+        &format!(
+            "user = \"mockuser\"\nlist = \"anime\"\nfile = \"{}\"\ntoken_file = \"{}\"\n",
+            dir.join("anime.xml").display(),
+            dir.join("missing-token").display()
+        ),
+    );
+    let export = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_mal-export-for-anilist"))
+            .args(["--endpoint", &server.url()])
+            .args(args)
+            .env_remove("ANILIST_ENDPOINT")
+            .env("ANILIST_TOKEN", "secret-env")
+            .env("XDG_CONFIG_HOME", &home)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        server.requests(|requests| {
+            requests
+                .last()
+                .unwrap()
+                .header("authorization")
+                .map(str::to_string)
+        })
+    };
+
+    assert_eq!(export(&[]).as_deref(), Some("Bearer secret-env"));
+
+    // a --token-file on the command line still beats the environment
+    let token_file = dir.join("token");
+    fs::write(&token_file, "secret-file\n").unwrap();
+    assert_eq!(
+        export(&["--token-file", token_file.to_str().unwrap()]).as_deref(),
+        Some("Bearer secret-file")
+    );
+}
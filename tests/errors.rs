@@ -0,0 +1,122 @@
+mod common;
+
+use common::{run_export, scratch_dir, MockServer, Response};
+
+fn export_with(endpoint: &str, name: &str) -> (i32, String) {
+    let dir = scratch_dir(name);
+    let file = dir.join("anime.xml");
+    let output = run_export(
+        endpoint,
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+            "--max-retries",
+            "0",
+        ],
+    );
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn graphql_errors_are_reported() {
+    let server = MockServer::start(|_| {
+        Response::json(
+            404,
+            r#"{"errors":[{"message":"Not Found.","status":404}],"data":{"User":null}}"#.into(),
+        )
+    });
+    let (code, stderr) = export_with(&server.url(), "graphql_errors");
+    assert_eq!(code, 6);
+    assert!(
+        stderr.contains("couldn't be found on AniList"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("AniList says: Not Found. (status 404)"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("Panic"), "{}", stderr);
+}
+
+#[test]
+fn known_graphql_errors_get_specific_diagnostics() {
+    let cases = [
+        (404, "Private User", "profile or list is private"),
+        (400, "Invalid token", "rejected the OAuth token"),
+        (401, "Unauthorized.", "rejected the OAuth token"),
+        (429, "Too Many Requests.", "rate limit was hit"),
+        (500, "Internal Server Error", "rejected the query"),
+    ];
+    for (status, message, diagnostic) in cases {
+        let body = format!(
+            r#"{{"errors":[{{"message":"{}","status":{}}}],"data":null}}"#,
+            message, status
+        );
+        let server = MockServer::start(move |_| Response::json(status, body.clone()));
+        let (code, stderr) = export_with(&server.url(), "known_graphql_errors");
+        assert_eq!(code, 6);
+        assert!(stderr.contains(diagnostic), "{}", stderr);
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+}
+
+#[test]
+fn http_errors_without_a_body_are_reported() {
+    let server = MockServer::start(|_| Response::json(502, "Bad Gateway".into()));
+    let (code, stderr) = export_with(&server.url(), "http_errors");
+    assert_eq!(code, 5);
+    assert!(stderr.contains("502"), "{}", stderr);
+}
+
+#[test]
+fn missing_data_is_reported() {
+    let server = MockServer::start(|_| Response::json(200, r#"{"data":{"User":null}}"#.into()));
+    let (code, stderr) = export_with(&server.url(), "missing_data");
+    assert_eq!(code, 8);
+    assert!(stderr.contains("anime statistics"), "{}", stderr);
+}
+
+#[test]
+fn unreachable_endpoint_is_a_transport_error() {
+    let (code, stderr) = export_with("http://127.0.0.1:1/", "transport_error");
+    assert_eq!(code, 4);
+    assert!(
+        stderr.starts_with("Error: couldn't reach the AniList API"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn failed_connections_are_retried() {
+    let dir = scratch_dir("transport_retry");
+    let output = run_export(
+        "http://127.0.0.1:1/",
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            dir.join("anime.xml").to_str().unwrap(),
+            "--max-retries",
+            "1",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Query failed (couldn't connect), retrying in 1s (1/1)"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,619 @@
+mod common;
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+use flate2::read::GzDecoder;
+use mal_export_for_anilist::malxml::MalExport;
+
+use common::{recorded, run, run_export, scratch_dir, MockServer, Response};
+
+#[test]
+fn anime_export_replays_recorded_responses() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("anime_export");
+    let file = dir.join("anime.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(server.request_count(), 2);
+
+    let xml = fs::read_to_string(&file).unwrap();
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<myanimelist>\n"));
+    assert!(xml.trim_end().ends_with("</myanimelist>"));
+    assert!(xml.contains("\t\t<user_name>mockuser</user_name>\n"));
+    assert!(xml.contains("\t\t<user_total_watching>2</user_total_watching>\n"));
+    assert!(xml.contains("\t\t<my_comments>Tom &amp; Jerry &lt;3</my_comments>\n"));
+    assert!(xml.contains("\t\t<my_tags>Favourites</my_tags>\n"));
+    assert!(xml.contains("\t\t<my_finish_date>2019-12-00</my_finish_date>\n"));
+    // entries unknown to MAL are kept, but commented out
+    assert!(xml.contains("<!--\n\t<anime>\n\t\t<series_animedb_id>0</series_animedb_id>\n"));
+    // custom list entries are only exported when hidden from the status lists
+    assert_eq!(
+        xml.matches("<series_title>Cowboy Bebop</series_title>")
+            .count(),
+        1
+    );
+    assert!(xml.contains("<my_tags>Rewatch later</my_tags>"));
+    assert!(xml.contains("<series_title>Adult Series</series_title>"));
+    assert!(xml.contains("<update_on_import>1</update_on_import>"));
+}
+
+#[test]
+fn export_flags_are_applied() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("export_flags");
+    let file = dir.join("anime.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+            "--no-nsfw",
+            "--no-update",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let xml = fs::read_to_string(&file).unwrap();
+    assert!(!xml.contains("Adult Series"));
+    assert!(!xml.contains("<update_on_import>1</update_on_import>"));
+    assert!(xml.contains("<update_on_import>0</update_on_import>"));
+}
+
+#[test]
+fn manga_export_replays_recorded_responses() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("manga_export");
+    let file = dir.join("manga.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "manga",
+            "-f",
+            file.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    server.requests(|requests| {
+        assert_eq!(requests[1].body["variables"]["type"], "MANGA");
+        assert_eq!(requests[1].body["variables"]["userName"], "mockuser");
+    });
+
+    let xml = fs::read_to_string(&file).unwrap();
+    assert!(xml.contains("\t\t<user_export_type>2</user_export_type>\n"));
+    assert!(xml.contains("\t\t<user_total_onhold>1</user_total_onhold>\n"));
+    assert!(xml.contains("\t\t<manga_title>Kimetsu no Yaiba</manga_title>\n"));
+    assert!(xml.contains("\t\t<my_read_volumes>27</my_read_volumes>\n"));
+    assert!(xml.contains("\t\t<my_status>On-Hold</my_status>\n"));
+}
+
+#[test]
+fn endpoint_can_be_set_through_the_environment() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("endpoint_env");
+    let file = dir.join("anime.xml");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mal-export-for-anilist"))
+        .args([
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+        ])
+        .env("ANILIST_ENDPOINT", server.url())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(server.request_count(), 2);
+    assert!(file.exists());
+}
+
+fn generated_entry(id: u64) -> serde_json::Value {
+    serde_json::json!({
+        "id": id,
+        "status": "COMPLETED",
+        "repeat": 0,
+        "progress": 12,
+        "progressVolumes": null,
+        "customLists": null,
+        "hiddenFromStatusLists": false,
+        "startedAt": { "year": null, "month": null, "day": null },
+        "completedAt": { "year": null, "month": null, "day": null },
+        "createdAt": 0,
+        "updatedAt": 0,
+        "score": 0,
+        "notes": null,
+        "media": {
+            "id": id,
+            "idMal": id,
+            "isAdult": false,
+            "title": { "romaji": format!("Series {}", id) },
+            "format": "TV",
+            "episodes": 12,
+            "chapters": null,
+            "volumes": null
+        },
+        "priority": 0
+    })
+}
+
+#[test]
+fn large_lists_are_fetched_in_chunks() {
+    const TOTAL: u64 = 1234;
+    let server = MockServer::start(|request| {
+        if !request.query().contains("MediaListCollection") {
+            return Response::json(200, recorded(request));
+        }
+        let chunk = request.body["variables"]["chunk"].as_u64().unwrap();
+        let per_chunk = request.body["variables"]["perChunk"].as_u64().unwrap();
+        let first = (chunk - 1) * per_chunk;
+        let last = (first + per_chunk).min(TOTAL);
+        let entries: Vec<_> = (first..last).map(|id| generated_entry(id + 1)).collect();
+        let body = serde_json::json!({
+            "data": {
+                "MediaListCollection": {
+                    "user": { "id": 5123456 },
+                    "hasNextChunk": last < TOTAL,
+                    "lists": [{ "entries": entries, "isCustomList": false }]
+                }
+            }
+        });
+        Response::json(200, body.to_string())
+    });
+    let dir = scratch_dir("chunked_export");
+    let file = dir.join("anime.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    server.requests(|requests| {
+        let chunks: Vec<_> = requests[1..]
+            .iter()
+            .map(|request| request.body["variables"]["chunk"].as_u64().unwrap())
+            .collect();
+        assert_eq!(chunks, [1, 2, 3]);
+    });
+
+    let xml = fs::read_to_string(&file).unwrap();
+    assert_eq!(xml.matches("\t<anime>\n").count(), TOTAL as usize);
+    assert!(xml.contains("<series_title>Series 1</series_title>"));
+    assert!(xml.contains("<series_title>Series 1234</series_title>"));
+}
+
+#[test]
+fn csv_export_has_one_row_per_entry() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("csv_export");
+    let file = dir.join("anime.csv");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+            "--format",
+            "csv",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let csv = fs::read_to_string(&file).unwrap();
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        rows[0],
+        "mal_id,anilist_id,title,format,status,progress,progress_volumes,score,started_at,completed_at,repeat,notes,custom_lists"
+    );
+    assert_eq!(rows.len(), 6);
+    assert_eq!(
+        rows[1],
+        "1,1,Cowboy Bebop,TV,COMPLETED,26,,9,2021-04-03,2021-06-19,1,Tom & Jerry <3,Favourites"
+    );
+    assert_eq!(
+        rows[2],
+        ",98765,Chuugoku Original Movie,MOVIE,COMPLETED,1,,7.5,,2019-12-00,0,,"
+    );
+    assert!(rows[3].ends_with(",0,\"dub, then \"\"sub\"\"\","));
+    assert!(rows.contains(&"5114,5114,Hagane no Renkinjutsushi: FULLMETAL ALCHEMIST,TV,PLANNING,0,,0,,,0,,Rewatch later"));
+}
+
+#[test]
+fn json_export_keeps_every_field() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("json_export");
+    let file = dir.join("anime.json");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+            "--format",
+            "json",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let export: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+    let recorded: serde_json::Value =
+        serde_json::from_str(&common::fixture("list_anime.json")).unwrap();
+    let stats: serde_json::Value =
+        serde_json::from_str(&common::fixture("user_anime.json")).unwrap();
+
+    assert_eq!(export["type"], "ANIME");
+    assert_eq!(export["user"]["id"], 5123456);
+    assert_eq!(
+        export["user"]["statistics"],
+        stats["data"]["User"]["statistics"]["anime"]
+    );
+
+    let entries = export["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 5);
+    for entry in entries {
+        let mut original = recorded["data"]["MediaListCollection"]["lists"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|list| list["entries"].as_array().unwrap())
+            .find(|original| original["id"] == entry["id"])
+            .unwrap()
+            .clone();
+        let mut entry = entry.clone();
+        // integer scores come back as floats
+        assert_eq!(entry["score"].as_f64(), original["score"].as_f64());
+        entry["score"].take();
+        original["score"].take();
+        assert_eq!(entry, original);
+    }
+}
+
+#[test]
+fn both_lists_are_exported_from_a_template() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("list_all_template");
+    let template = dir.join("{user}-{list}.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "all",
+            "-f",
+            template.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(server.request_count(), 4);
+
+    let anime = fs::read_to_string(dir.join("mockuser-anime.xml")).unwrap();
+    assert!(anime.contains("\t\t<user_export_type>1</user_export_type>\n"));
+    assert!(anime.contains("<series_title>Cowboy Bebop</series_title>"));
+    let manga = fs::read_to_string(dir.join("mockuser-manga.xml")).unwrap();
+    assert!(manga.contains("\t\t<user_export_type>2</user_export_type>\n"));
+    assert!(manga.contains("<manga_title>Kimetsu no Yaiba</manga_title>"));
+}
+
+#[test]
+fn both_lists_are_exported_into_a_directory() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("list_all_directory");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "all",
+            "-f",
+            dir.to_str().unwrap(),
+            "--format",
+            "csv",
+            "--save-raw",
+            dir.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    for name in [
+        "mockuser-anime.csv",
+        "mockuser-manga.csv",
+        "mockuser-anime-raw.json",
+        "mockuser-manga-raw.json",
+    ] {
+        assert!(dir.join(name).exists(), "{} is missing", name);
+    }
+
+    // a single file can't hold both lists
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "all",
+            "-f",
+            dir.join("lists.xml").to_str().unwrap(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(!dir.join("lists.xml").exists());
+}
+
+#[test]
+fn export_can_be_written_to_stdout() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("stdout_export");
+    let file = dir.join("anime.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let output = run_export(&server.url(), &["-u", "mockuser", "-l", "anime", "-f", "-"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        fs::read_to_string(&file).unwrap()
+    );
+    assert!(!dir.join("-").exists());
+
+    // two documents would run into each other
+    let output = run_export(&server.url(), &["-u", "mockuser", "-l", "all", "-f", "-"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn failed_exports_keep_the_previous_file() {
+    let dir = scratch_dir("atomic_export");
+    let file = dir.join("anime.xml");
+    let args = [
+        "-u",
+        "mockuser",
+        "-l",
+        "anime",
+        "-f",
+        file.to_str().unwrap(),
+        "--max-retries",
+        "0",
+    ];
+
+    let server = MockServer::replay();
+    let output = run_export(&server.url(), &args);
+    assert!(output.status.success(), "{:?}", output);
+    let previous = fs::read_to_string(&file).unwrap();
+
+    let failing = MockServer::start(|request| {
+        if request.query().contains("MediaListCollection") {
+            Response::json(500, String::new())
+        } else {
+            Response::json(200, recorded(request))
+        }
+    });
+    let output = run_export(&failing.url(), &args);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    assert_eq!(fs::read_to_string(&file).unwrap(), previous);
+
+    // nothing is left behind next to the export
+    let names: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["anime.xml"]);
+}
+
+#[test]
+fn gz_files_are_compressed() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("gzip_export");
+    let plain = dir.join("anime.xml");
+    let compressed = dir.join("anime.xml.gz");
+    let export = |args: &[&str]| {
+        let mut all = vec!["-u", "mockuser", "-l", "anime"];
+        all.extend(args);
+        let output = run_export(&server.url(), &all);
+        assert!(output.status.success(), "{:?}", output);
+    };
+    let gunzip = |path: &Path| {
+        let mut xml = String::new();
+        GzDecoder::new(fs::File::open(path).unwrap())
+            .read_to_string(&mut xml)
+            .unwrap();
+        xml
+    };
+
+    export(&["-f", plain.to_str().unwrap()]);
+    export(&["-f", compressed.to_str().unwrap()]);
+    let xml = fs::read_to_string(&plain).unwrap();
+    assert_eq!(gunzip(&compressed), xml);
+
+    // --gzip names the files of a directory export accordingly
+    let output_dir = dir.join("out");
+    fs::create_dir(&output_dir).unwrap();
+    export(&["-f", output_dir.to_str().unwrap(), "--gzip"]);
+    assert_eq!(gunzip(&output_dir.join("mockuser-anime.xml.gz")), xml);
+
+    let output = run(&["validate", compressed.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn text_fields_can_be_wrapped_in_cdata() {
+    let server = MockServer::start(|request| {
+        Response::json(
+            200,
+            recorded(request).replace("dub, then \\\"sub\\\"", "a]]>b & <c>"),
+        )
+    });
+    let dir = scratch_dir("cdata_export");
+    let file = dir.join("anime.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+            "--cdata",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let xml = fs::read_to_string(&file).unwrap();
+    assert!(xml.contains("\t\t<series_title><![CDATA[Cowboy Bebop]]></series_title>\n"));
+    assert!(xml.contains("\t\t<my_comments><![CDATA[Tom & Jerry <3]]></my_comments>\n"));
+    assert!(xml.contains("<my_comments><![CDATA[a]]]]><![CDATA[>b & <c>]]></my_comments>"));
+    assert!(xml.contains("\t\t<my_tags><![CDATA[Favourites]]></my_tags>\n"));
+    // fields that aren't free text are left as they are
+    assert!(xml.contains("\t\t<my_status>Completed</my_status>\n"));
+
+    // the split sections still read back as the original text
+    let output = run(&["validate", file.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn characters_xml_forbids_are_removed() {
+    let server = MockServer::start(|request| {
+        Response::json(
+            200,
+            recorded(request).replace(
+                "dub, then \\\"sub\\\"",
+                "bell\\u0007 and nul\\u0000, tab\\tkept",
+            ),
+        )
+    });
+    let dir = scratch_dir("illegal_characters");
+    let file = dir.join("anime.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let xml = fs::read_to_string(&file).unwrap();
+    assert!(xml.contains("<my_comments>bell and nul, tab\tkept</my_comments>"));
+    assert!(!xml.contains(['\u{0}', '\u{7}']));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr.matches("Warning: removed characters").count(),
+        1,
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("from the notes of Naruto (AniList id 20)"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn commented_out_entries_survive_double_hyphens() {
+    const TITLE: &str = "Movie -- the <!-- end --> cut-";
+    let server = MockServer::start(|request| {
+        Response::json(
+            200,
+            recorded(request).replace("Chuugoku Original Movie", TITLE),
+        )
+    });
+    let dir = scratch_dir("comment_hyphens");
+
+    for (name, flags) in [("escaped.xml", &[][..]), ("cdata.xml", &["--cdata"][..])] {
+        let file = dir.join(name);
+        let mut args = vec![
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+        ];
+        args.extend(flags);
+        let output = run_export(&server.url(), &args);
+        assert!(output.status.success(), "{:?}", output);
+
+        // every comment ends where it is meant to
+        let xml = fs::read_to_string(&file).unwrap();
+        for comment in xml.split("<!--").skip(1) {
+            let (inside, _) = comment.split_once("-->").unwrap();
+            assert!(!inside.contains("--"), "{}", inside);
+            assert!(inside.ends_with('\n'), "{}", inside);
+        }
+        assert_eq!(xml.matches("<!--").count(), xml.matches("-->").count());
+
+        // and the entry reads back as it was once uncommented
+        let uncommented = xml
+            .replace("<!--\n\t<anime>", "\t<anime>")
+            .replace("\t</anime>\n-->\n", "\t</anime>\n");
+        let export = MalExport::parse(&uncommented).unwrap();
+        let entry = export
+            .entries
+            .iter()
+            .find(|entry| entry.id() == Some("0"))
+            .unwrap();
+        assert_eq!(entry.title(), Some(TITLE));
+    }
+}
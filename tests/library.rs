@@ -0,0 +1,162 @@
+mod common;
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use common::{recorded, MockServer, Response};
+use mal_export_for_anilist::{
+    AniListClient, Collection, Exporter, ListType, MediaEntry, ModifiedEntry, OutputWriter,
+    RetryReason,
+};
+
+#[tokio::test]
+async fn exporter_renders_to_a_string() {
+    let server = MockServer::replay();
+    let client = AniListClient::new(&server.url(), "", 0).unwrap();
+
+    let xml = Exporter::new(ListType::Manga)
+        .export_string(&client, "mockuser")
+        .await
+        .unwrap();
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<myanimelist>\n"));
+    assert!(xml.contains("<manga_title>Berserk</manga_title>"));
+    assert!(xml.ends_with("</myanimelist>\n"));
+}
+
+#[tokio::test]
+async fn exporter_writes_to_any_writer() {
+    let server = MockServer::replay();
+    let client = AniListClient::new(&server.url(), "", 0).unwrap();
+    let exporter = Exporter {
+        nsfw: false,
+        ..Exporter::new(ListType::Anime)
+    };
+
+    let mut buffer: Vec<u8> = Vec::new();
+    exporter
+        .export(&client, "mockuser", &mut buffer)
+        .await
+        .unwrap();
+    let xml = String::from_utf8(buffer).unwrap();
+    assert!(xml.contains("<series_title>Cowboy Bebop</series_title>"));
+    assert!(!xml.contains("Adult Series"));
+}
+
+#[tokio::test]
+async fn collection_exposes_the_typed_model() {
+    let server = MockServer::replay();
+    let client = AniListClient::new(&server.url(), "", 0).unwrap();
+
+    let collection = client
+        .collection("mockuser", ListType::Anime)
+        .await
+        .unwrap();
+    assert_eq!(collection.user.id, 5123456);
+    assert_eq!(collection.user.statistics.count, 5);
+    assert_eq!(collection.lists.len(), 3);
+
+    let titles: Vec<&str> = Exporter::new(ListType::Anime)
+        .entries(&collection)
+        .map(|entry| entry.media.title.romaji.as_str())
+        .collect();
+    assert_eq!(
+        titles,
+        [
+            "Cowboy Bebop",
+            "Chuugoku Original Movie",
+            "Naruto",
+            "Adult Series",
+            "Hagane no Renkinjutsushi: FULLMETAL ALCHEMIST"
+        ]
+    );
+}
+
+struct TitleWriter;
+
+impl OutputWriter for TitleWriter {
+    fn header(&mut self, f: &mut dyn Write, collection: &Collection) -> io::Result<()> {
+        writeln!(f, "# {}", collection.user.name)
+    }
+
+    fn entry(&mut self, f: &mut dyn Write, entry: &MediaEntry) -> io::Result<()> {
+        writeln!(f, "{}", entry.media.title.romaji)
+    }
+
+    fn footer(&mut self, f: &mut dyn Write) -> io::Result<()> {
+        writeln!(f, "# end")
+    }
+}
+
+#[tokio::test]
+async fn custom_backends_reuse_the_filtering() {
+    let server = MockServer::replay();
+    let client = AniListClient::new(&server.url(), "", 0).unwrap();
+    let collection = client
+        .collection("mockuser", ListType::Manga)
+        .await
+        .unwrap();
+
+    let mut buffer: Vec<u8> = Vec::new();
+    Exporter::new(ListType::Manga)
+        .write_with(&mut TitleWriter, &collection, &mut buffer)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        "# mockuser\nBerserk\nKimetsu no Yaiba\n# end\n"
+    );
+}
+
+#[tokio::test]
+async fn modified_entries_are_returned_instead_of_printed() {
+    let server = MockServer::start(|request| {
+        Response::json(
+            200,
+            recorded(request).replace("Cowboy Bebop\"", "Cowboy\\u0007 Bebop\""),
+        )
+    });
+    let client = AniListClient::new(&server.url(), "", 0).unwrap();
+    let collection = client
+        .collection("mockuser", ListType::Anime)
+        .await
+        .unwrap();
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let modified = Exporter::new(ListType::Anime)
+        .write(&collection, &mut buffer)
+        .unwrap();
+    assert_eq!(
+        modified,
+        [ModifiedEntry {
+            id: 1,
+            title: "Cowboy Bebop".to_string(),
+            fields: vec!["title"],
+        }]
+    );
+}
+
+#[tokio::test]
+async fn retries_are_reported_to_the_callback() {
+    let calls = AtomicUsize::new(0);
+    let server = MockServer::start(move |request| {
+        if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            Response::json(503, String::new()).with_header("Retry-After", "0")
+        } else {
+            Response::json(200, recorded(request))
+        }
+    });
+    let retries = Arc::new(Mutex::new(Vec::new()));
+    let client = AniListClient::new(&server.url(), "", 2).unwrap().on_retry({
+        let retries = Arc::clone(&retries);
+        move |retry| retries.lock().unwrap().push(retry.clone())
+    });
+
+    client
+        .collection("mockuser", ListType::Manga)
+        .await
+        .unwrap();
+    let retries = retries.lock().unwrap();
+    assert_eq!(retries.len(), 1);
+    assert!(matches!(retries[0].reason, RetryReason::Status(status) if status == 503));
+    assert_eq!((retries[0].attempt, retries[0].max_retries), (1, 2));
+}
@@ -0,0 +1,528 @@
+mod common;
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStderr, Command, Output, Stdio};
+use std::sync::atomic::{AtomicU16, Ordering};
+
+use common::{scratch_dir, MockServer, Response};
+
+/// A port for the redirect listener. They are taken from below the ephemeral range, so that the
+/// mock servers the OS assigns ports to can't grab one between this check and the listener
+/// binding it.
+fn free_port() -> u16 {
+    static NEXT: AtomicU16 = AtomicU16::new(28919);
+    loop {
+        let port = NEXT.fetch_add(1, Ordering::Relaxed);
+        if TcpListener::bind(("127.0.0.1", port)).is_ok() {
+            return port;
+        }
+    }
+}
+
+/// Starts an export logging in through the authorization code grant and returns the process
+/// along with the authorize URL it printed, and its stderr to read the rest of the output from.
+fn start_login(
+    endpoint: &str,
+    token_endpoint: &str,
+    redirect_uri: &str,
+    name: &str,
+) -> (Child, String, BufReader<ChildStderr>) {
+    let dir = scratch_dir(name);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mal-export-for-anilist"))
+        .args(["--endpoint", endpoint])
+        .args(["-u", "mockuser", "-l", "anime", "--oauth"])
+        .args(["-f", dir.join("anime.xml").to_str().unwrap()])
+        .args(["--client-id", "4242", "--client-secret", "hunter2"])
+        .args(["--redirect-uri", redirect_uri])
+        .args(["--token-endpoint", token_endpoint])
+        .args(["--max-retries", "0"])
+        .env_remove("ANILIST_ENDPOINT")
+        .env_remove("ANILIST_CLIENT_SECRET")
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    while !line.contains("https://") {
+        line.clear();
+        assert_ne!(stderr.read_line(&mut line).unwrap(), 0, "no authorize URL");
+    }
+    let url = line[line.find("https://").unwrap()..].trim().to_string();
+    (child, url, stderr)
+}
+
+fn query_param<'a>(url: &'a str, name: &str) -> &'a str {
+    url.split(['?', '&'])
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+        .unwrap_or_else(|| panic!("{} has no {}", url, name))
+}
+
+/// Plays the browser being redirected back from AniList.
+fn redirect(port: u16, target: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: 127.0.0.1:{}\r\n\r\n",
+        target, port
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn authorization_code_is_exchanged_for_a_token() {
+    let api = MockServer::replay();
+    let token_endpoint = MockServer::start(|_| {
+        Response::json(
+            200,
+            r#"{"token_type":"Bearer","expires_in":31536000,"access_token":"fresh-token","refresh_token":"unused"}"#.into(),
+        )
+    });
+    let port = free_port();
+    let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+
+    let (child, url, _stderr) = start_login(
+        &api.url(),
+        &token_endpoint.url(),
+        &redirect_uri,
+        "oauth_code",
+    );
+    assert!(url.starts_with("https://anilist.co/api/v2/oauth/authorize?"));
+    assert_eq!(query_param(&url, "client_id"), "4242");
+    assert_eq!(query_param(&url, "response_type"), "code");
+    let state = query_param(&url, "state");
+
+    assert!(redirect(port, "/favicon.ico").starts_with("HTTP/1.1 404"));
+    let page = redirect(port, &format!("/callback?code=abc123&state={}", state));
+    assert!(page.starts_with("HTTP/1.1 200"), "{}", page);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    token_endpoint.requests(|requests| {
+        assert_eq!(requests.len(), 1);
+        let body = &requests[0].body;
+        assert_eq!(body["grant_type"], "authorization_code");
+        assert_eq!(body["client_id"], "4242");
+        assert_eq!(body["client_secret"], "hunter2");
+        assert_eq!(body["redirect_uri"], redirect_uri);
+        assert_eq!(body["code"], "abc123");
+    });
+    api.requests(|requests| {
+        for request in requests {
+            assert_eq!(request.header("authorization"), Some("Bearer fresh-token"));
+        }
+    });
+}
+
+#[test]
+fn idle_connections_dont_hold_up_the_redirect() {
+    let api = MockServer::replay();
+    let token_endpoint =
+        MockServer::start(|_| Response::json(200, r#"{"access_token":"fresh-token"}"#.into()));
+    let port = free_port();
+    let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+
+    let (child, url, _stderr) = start_login(
+        &api.url(),
+        &token_endpoint.url(),
+        &redirect_uri,
+        "oauth_preconnect",
+    );
+    // browsers open connections ahead of time and may never send anything on them
+    let _idle = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let page = redirect(
+        port,
+        &format!("/callback?code=abc123&state={}", query_param(&url, "state")),
+    );
+    assert!(page.starts_with("HTTP/1.1 200"), "{}", page);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(token_endpoint.request_count(), 1);
+}
+
+#[test]
+fn redirects_from_another_login_are_rejected() {
+    let api = MockServer::replay();
+    let token_endpoint = MockServer::start(|_| Response::json(500, String::new()));
+    let port = free_port();
+    let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+
+    let (child, _, mut stderr) = start_login(
+        &api.url(),
+        &token_endpoint.url(),
+        &redirect_uri,
+        "oauth_state",
+    );
+    let page = redirect(port, "/callback?code=abc123&state=forged");
+    assert!(page.starts_with("HTTP/1.1 400"), "{}", page);
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(12));
+    let mut rest = String::new();
+    stderr.read_to_string(&mut rest).unwrap();
+    assert!(
+        rest.contains("doesn't belong to this login attempt"),
+        "{}",
+        rest
+    );
+    assert_eq!(token_endpoint.request_count(), 0);
+    assert_eq!(api.request_count(), 0);
+}
+
+/// The binary with `config` as its config directory and none of the environment variables it
+/// reads set.
+fn exporter(config: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mal-export-for-anilist"));
+    command
+        .env_remove("ANILIST_ENDPOINT")
+        .env_remove("ANILIST_CLIENT_SECRET")
+        .env_remove("ANILIST_CLIENT_ID")
+        .env_remove("ANILIST_TOKEN")
+        .env("XDG_CONFIG_HOME", config);
+    command
+}
+
+/// Runs the binary with `config` as its config directory, feeding it `stdin`.
+fn run_with_config(config: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = exporter(config)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn login_stores_the_token_for_later_exports() {
+    let api = MockServer::replay();
+    let dir = scratch_dir("login");
+    let config = dir.join("config");
+    let file = dir.join("anime.xml");
+    let export = [
+        "--endpoint",
+        &api.url(),
+        "-u",
+        "mockuser",
+        "-l",
+        "anime",
+        "-f",
+        file.to_str().unwrap(),
+    ];
+
+    let output = run_with_config(&config, &["login"], "pasted-token\n");
+    assert!(output.status.success(), "{:?}", output);
+    let token_file = config.join("mal-export-for-anilist").join("token.json");
+    let stored: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&token_file).unwrap()).unwrap();
+    assert_eq!(stored["access_token"], "pasted-token");
+    assert!(stored["expires_at"].as_i64().unwrap() > 0);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&token_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let output = run_with_config(&config, &export, "");
+    assert!(output.status.success(), "{:?}", output);
+    api.requests(|requests| {
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("Bearer pasted-token")
+        );
+    });
+
+    let output = run_with_config(&config, &["logout"], "");
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!token_file.exists());
+
+    let output = run_with_config(&config, &export, "");
+    assert!(output.status.success(), "{:?}", output);
+    api.requests(|requests| {
+        assert_eq!(requests.len(), 5);
+        assert_eq!(requests[3].header("authorization"), None);
+    });
+}
+
+#[test]
+fn failing_to_store_the_token_names_the_file() {
+    let dir = scratch_dir("login_unwritable");
+    let config = dir.join("config");
+    let token_file = config.join("mal-export-for-anilist").join("token.json");
+    // a directory where the token should go
+    fs::create_dir_all(&token_file).unwrap();
+
+    let output = run_with_config(&config, &["login"], "pasted-token\n");
+    assert_eq!(output.status.code(), Some(12), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "couldn't save the token to {}",
+            token_file.display()
+        )),
+        "{}",
+        stderr
+    );
+}
+
+/// Sets up a config directory under `dir` as `login` would have left it.
+fn store_token(dir: &Path, token: &str, expires_at: i64) -> PathBuf {
+    let config = dir.join("config");
+    fs::create_dir_all(config.join("mal-export-for-anilist")).unwrap();
+    fs::write(
+        config.join("mal-export-for-anilist").join("token.json"),
+        serde_json::json!({ "access_token": token, "expires_at": expires_at }).to_string(),
+    )
+    .unwrap();
+    config
+}
+
+#[test]
+fn expired_tokens_are_not_sent() {
+    let api = MockServer::replay();
+    let dir = scratch_dir("login_expired");
+    let config = store_token(&dir, "old-token", 1600000000);
+
+    let output = run_with_config(
+        &config,
+        &[
+            "--endpoint",
+            &api.url(),
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            dir.join("anime.xml").to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("expired on 2020-09-13"), "{}", stderr);
+    api.requests(|requests| {
+        assert_eq!(requests[0].header("authorization"), None);
+    });
+}
+
+#[test]
+fn tokens_of_another_account_get_a_warning() {
+    let api = MockServer::replay();
+    let dir = scratch_dir("viewer_mismatch");
+    let config = store_token(&dir, "valid-token", i64::MAX / 2);
+
+    let output = run_with_config(
+        &config,
+        &[
+            "--endpoint",
+            &api.url(),
+            "-u",
+            "someoneelse",
+            "-l",
+            "anime",
+            "-f",
+            dir.join("anime.xml").to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Authorized as mockuser"), "{}", stderr);
+    assert!(
+        stderr.contains(
+            "Warning: the token belongs to mockuser, hidden entries of someoneelse won't be exported"
+        ),
+        "{}",
+        stderr
+    );
+    api.requests(|requests| assert!(requests[0].query().contains("Viewer")));
+}
+
+#[test]
+fn rejected_tokens_fail_before_exporting() {
+    let api = MockServer::start(|_| {
+        Response::json(
+            400,
+            r#"{"errors":[{"message":"Invalid token","status":400}],"data":null}"#.into(),
+        )
+    });
+    let dir = scratch_dir("viewer_rejected");
+    let config = store_token(&dir, "revoked-token", i64::MAX / 2);
+
+    let output = run_with_config(
+        &config,
+        &[
+            "--endpoint",
+            &api.url(),
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            dir.join("anime.xml").to_str().unwrap(),
+            "--max-retries",
+            "0",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(6));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("AniList rejected the OAuth token"),
+        "{}",
+        stderr
+    );
+    assert_eq!(api.request_count(), 1);
+    assert!(!dir.join("anime.xml").exists());
+}
+
+#[test]
+fn client_id_comes_from_flags_environment_or_config() {
+    let dir = scratch_dir("client_id");
+    let config = dir.join("config");
+    fs::create_dir_all(config.join("mal-export-for-anilist")).unwrap();
+    fs::write(
+        config.join("mal-export-for-anilist").join("config.toml"),
+        "[oauth]\nclient_id = \"1001\"\n",
+    )
+    .unwrap();
+
+    let authorize_url = |env: Option<&str>, args: &[&str]| {
+        let mut command = exporter(&config);
+        command.arg("login").args(args).stdin(Stdio::null());
+        if let Some(id) = env {
+            command.env("ANILIST_CLIENT_ID", id);
+        }
+        let output = command.output().unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        let line = &stderr[stderr.find("https://").unwrap()..];
+        line.lines().next().unwrap().to_string()
+    };
+
+    assert_eq!(query_param(&authorize_url(None, &[]), "client_id"), "1001");
+    assert_eq!(
+        query_param(&authorize_url(Some("2002"), &[]), "client_id"),
+        "2002"
+    );
+    assert_eq!(
+        query_param(
+            &authorize_url(Some("2002"), &["--client-id", "3003"]),
+            "client_id"
+        ),
+        "3003"
+    );
+}
+
+#[test]
+fn invalid_config_files_are_reported() {
+    let dir = scratch_dir("config_invalid");
+    let config = dir.join("config");
+    fs::create_dir_all(config.join("mal-export-for-anilist")).unwrap();
+    fs::write(
+        config.join("mal-export-for-anilist").join("config.toml"),
+        "[oauth]\nclient = \"1001\"\n",
+    )
+    .unwrap();
+
+    let output = run_with_config(&config, &["login"], "");
+    assert_eq!(output.status.code(), Some(13));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("couldn't load the config file"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn tokens_can_be_given_without_a_prompt() {
+    let api = MockServer::replay();
+    let dir = scratch_dir("token_sources");
+    let config = dir.join("config");
+    let export = |command: &mut Command| {
+        let output = command
+            .args(["--endpoint", &api.url(), "-u", "mockuser", "-l", "anime"])
+            .args(["-f", dir.join("anime.xml").to_str().unwrap()])
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let output = format!(
+            "{}{}",
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap()
+        );
+        assert!(
+            !output.contains("secret-"),
+            "the token was echoed: {}",
+            output
+        );
+        api.requests(|requests| {
+            requests
+                .last()
+                .unwrap()
+                .header("authorization")
+                .map(str::to_string)
+        })
+    };
+
+    let token = export(exporter(&config).env("ANILIST_TOKEN", "secret-env"));
+    assert_eq!(token.as_deref(), Some("Bearer secret-env"));
+
+    let raw = dir.join("raw-token");
+    fs::write(&raw, "secret-file\n").unwrap();
+    let token = export(exporter(&config).args(["--token-file", raw.to_str().unwrap()]));
+    assert_eq!(token.as_deref(), Some("Bearer secret-file"));
+
+    // the file login stores works too
+    let stored = store_token(&dir.join("elsewhere"), "secret-stored", i64::MAX / 2);
+    let stored = stored.join("mal-export-for-anilist").join("token.json");
+    let token = export(
+        exporter(&config)
+            .env("ANILIST_TOKEN", "secret-env")
+            .args(["--token-file", stored.to_str().unwrap()]),
+    );
+    assert_eq!(token.as_deref(), Some("Bearer secret-stored"));
+}
+
+#[test]
+fn empty_token_files_are_rejected() {
+    let dir = scratch_dir("token_file_empty");
+    let empty = dir.join("token");
+    fs::write(&empty, "\n").unwrap();
+
+    let output = run_with_config(
+        &dir.join("config"),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            dir.join("anime.xml").to_str().unwrap(),
+            "--token-file",
+            empty.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(12));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("holds no token"), "{}", stderr);
+}
@@ -0,0 +1,195 @@
+mod common;
+
+use std::fs;
+
+use common::{run_export, scratch_dir, MockServer};
+
+// nothing listens here, so any query would fail
+const OFFLINE: &str = "http://127.0.0.1:1/";
+
+#[test]
+fn saved_responses_can_be_exported_again() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("save_raw");
+    let live = dir.join("live.xml");
+    let raw = dir.join("raw.json");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            live.to_str().unwrap(),
+            "--save-raw",
+            raw.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let snapshot: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&raw).unwrap()).unwrap();
+    assert_eq!(snapshot["type"], "ANIME");
+    assert_eq!(snapshot["responses"].as_array().unwrap().len(), 2);
+
+    let offline = dir.join("offline.xml");
+    let output = run_export(
+        OFFLINE,
+        &[
+            "--input",
+            raw.to_str().unwrap(),
+            "-f",
+            offline.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        fs::read_to_string(&live).unwrap(),
+        fs::read_to_string(&offline).unwrap()
+    );
+
+    // other flags and formats apply to the saved data as well
+    let csv = dir.join("offline.csv");
+    let output = run_export(
+        OFFLINE,
+        &[
+            "--input",
+            raw.to_str().unwrap(),
+            "-f",
+            csv.to_str().unwrap(),
+            "--format",
+            "csv",
+            "--no-nsfw",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let csv = fs::read_to_string(&csv).unwrap();
+    assert_eq!(csv.lines().count(), 5);
+    assert!(!csv.contains("Adult Series"));
+}
+
+#[test]
+fn json_exports_can_be_rendered_as_xml() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("json_input");
+    let live = dir.join("live.xml");
+    let json = dir.join("backup.json");
+    for (file, format) in [(&live, "xml"), (&json, "json")] {
+        let output = run_export(
+            &server.url(),
+            &[
+                "-u",
+                "mockuser",
+                "-l",
+                "manga",
+                "-f",
+                file.to_str().unwrap(),
+                "--format",
+                format,
+            ],
+        );
+        assert!(output.status.success(), "{:?}", output);
+    }
+
+    let offline = dir.join("offline.xml");
+    let output = run_export(
+        OFFLINE,
+        &[
+            "--input",
+            json.to_str().unwrap(),
+            "-f",
+            offline.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        fs::read_to_string(&live).unwrap(),
+        fs::read_to_string(&offline).unwrap()
+    );
+}
+
+#[test]
+fn input_of_the_wrong_type_is_rejected() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("wrong_input");
+    let raw = dir.join("raw.json");
+    let file = dir.join("export.xml");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "manga",
+            "-f",
+            file.to_str().unwrap(),
+            "--save-raw",
+            raw.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = run_export(
+        OFFLINE,
+        &[
+            "--input",
+            raw.to_str().unwrap(),
+            "-l",
+            "anime",
+            "-f",
+            file.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("holds the manga list, but --list anime was given"),
+        "{}",
+        stderr
+    );
+
+    // all includes the one list the input holds
+    let output = run_export(
+        OFFLINE,
+        &[
+            "--input",
+            raw.to_str().unwrap(),
+            "-l",
+            "all",
+            "-f",
+            file.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn unwritable_raw_files_are_named() {
+    let server = MockServer::replay();
+    let dir = scratch_dir("save_raw_unwritable");
+    let raw = dir.join("missing").join("raw.json");
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "-u",
+            "mockuser",
+            "-l",
+            "anime",
+            "-f",
+            dir.join("anime.xml").to_str().unwrap(),
+            "--save-raw",
+            raw.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!("couldn't write the output: {}: ", raw.display())),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use common::{recorded, run_export, scratch_dir, MockServer, Response};
+use mal_export_for_anilist::ratelimit::RateLimiter;
+use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+fn export(server: &MockServer, name: &str, extra: &[&str]) -> std::process::Output {
+    let dir = scratch_dir(name);
+    let file = dir.join("anime.xml");
+    let mut args = vec![
+        "-u",
+        "mockuser",
+        "-l",
+        "anime",
+        "-f",
+        file.to_str().unwrap(),
+    ];
+    args.extend_from_slice(extra);
+    run_export(&server.url(), &args)
+}
+
+fn rate_limited() -> Response {
+    Response::json(
+        429,
+        r#"{"errors":[{"message":"Too Many Requests.","status":429}],"data":null}"#.into(),
+    )
+}
+
+#[test]
+fn rate_limited_queries_are_retried_after_the_requested_delay() {
+    let calls = AtomicUsize::new(0);
+    let server = MockServer::start(move |request| {
+        if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+            rate_limited().with_header("Retry-After", "0")
+        } else {
+            Response::json(200, recorded(request))
+        }
+    });
+
+    let output = export(&server, "retry_after", &[]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(server.request_count(), 4);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("retrying in 0s (1/5)"), "{}", stderr);
+}
+
+#[test]
+fn reset_header_is_used_without_retry_after() {
+    let calls = AtomicUsize::new(0);
+    let server = MockServer::start(move |request| {
+        if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            rate_limited()
+                .with_header("X-RateLimit-Remaining", "0")
+                .with_header("X-RateLimit-Reset", &now.as_secs().to_string())
+        } else {
+            Response::json(200, recorded(request))
+        }
+    });
+
+    let output = export(&server, "ratelimit_reset", &[]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(server.request_count(), 3);
+}
+
+#[test]
+fn retries_give_up_eventually() {
+    let server = MockServer::start(|_| rate_limited().with_header("Retry-After", "0"));
+
+    let output = export(&server, "retry_give_up", &["--max-retries", "2"]);
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(server.request_count(), 3);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("rate limit was hit"), "{}", stderr);
+}
+
+#[test]
+fn exhausted_window_delays_the_next_query() {
+    let seen: Arc<Mutex<Vec<Instant>>> = Arc::default();
+    let log = Arc::clone(&seen);
+    let server = MockServer::start(move |request| {
+        let mut seen = log.lock().unwrap();
+        seen.push(Instant::now());
+        let response = Response::json(200, recorded(request));
+        if seen.len() == 1 {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            response
+                .with_header("X-RateLimit-Remaining", "0")
+                .with_header("X-RateLimit-Reset", &(now.as_secs() + 2).to_string())
+        } else {
+            response
+        }
+    });
+
+    let output = export(&server, "ratelimit_window", &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    assert!(seen[1] - seen[0] >= Duration::from_secs(1));
+}
+
+#[test]
+fn retry_after_is_capped_at_the_window() {
+    let limiter = RateLimiter::new(5);
+    let mut headers = HeaderMap::new();
+    for retry_after in ["3600", "18446744073709551615"] {
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(retry_after));
+        assert_eq!(limiter.retry_delay(&headers, 0), Duration::from_secs(60));
+    }
+}
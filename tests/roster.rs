@@ -0,0 +1,151 @@
+mod common;
+
+use std::fs;
+
+use common::{recorded, run_export, scratch_dir, MockServer, Response};
+
+fn roster_server() -> MockServer {
+    MockServer::start(|request| {
+        let variables = &request.body["variables"];
+        let user = variables["name"]
+            .as_str()
+            .or(variables["userName"].as_str())
+            .unwrap();
+        if user == "ghost" {
+            return Response::json(
+                404,
+                r#"{"errors":[{"message":"Not Found.","status":404}],"data":{"User":null}}"#.into(),
+            );
+        }
+        Response::json(200, recorded(request))
+    })
+}
+
+#[test]
+fn every_user_of_a_toml_roster_is_exported() {
+    let server = roster_server();
+    let dir = scratch_dir("roster_toml");
+    let roster = dir.join("roster.toml");
+    fs::write(
+        &roster,
+        r#"
+users = [
+    "alice",
+    { user = "bob", list = "all", nsfw = false },
+]
+"#,
+    )
+    .unwrap();
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "--roster",
+            roster.to_str().unwrap(),
+            "-l",
+            "anime",
+            "-f",
+            dir.join("{user}-{list}.xml").to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(server.request_count(), 6);
+
+    let alice = fs::read_to_string(dir.join("alice-anime.xml")).unwrap();
+    assert!(alice.contains("<series_title>Adult Series</series_title>"));
+    let bob = fs::read_to_string(dir.join("bob-anime.xml")).unwrap();
+    assert!(!bob.contains("Adult Series"));
+    assert!(dir.join("bob-manga.xml").exists());
+    assert!(!dir.join("alice-manga.xml").exists());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Exported 2 of 2 users:"), "{}", stderr);
+}
+
+#[test]
+fn failed_users_are_summarized_without_stopping_the_batch() {
+    let server = roster_server();
+    let dir = scratch_dir("roster_text");
+    let roster = dir.join("roster.txt");
+    fs::write(
+        &roster,
+        "# nightly backups\nghost anime\n\nalice manga no-update  # only the manga list\n",
+    )
+    .unwrap();
+
+    let output = run_export(
+        &server.url(),
+        &[
+            "--roster",
+            roster.to_str().unwrap(),
+            "-f",
+            dir.to_str().unwrap(),
+            "--max-retries",
+            "0",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(11));
+    let manga = fs::read_to_string(dir.join("alice-manga.xml")).unwrap();
+    assert!(manga.contains("<update_on_import>0</update_on_import>"));
+    assert!(!dir.join("ghost-anime.xml").exists());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Exported 1 of 2 users:"), "{}", stderr);
+    assert!(stderr.contains("  ghost: failed, "), "{}", stderr);
+    assert!(stderr.contains("  alice: ok"), "{}", stderr);
+    assert!(stderr.contains("Error: 1 of 2 users couldn't be exported"));
+}
+
+#[test]
+fn a_roster_needs_a_path_per_user() {
+    let dir = scratch_dir("roster_template");
+    let roster = dir.join("roster.json");
+    fs::write(&roster, r#"{"users": ["alice", "bob"]}"#).unwrap();
+
+    let output = run_export(
+        "http://127.0.0.1:9",
+        &[
+            "--roster",
+            roster.to_str().unwrap(),
+            "-l",
+            "anime",
+            "-f",
+            dir.join("anime.xml").to_str().unwrap(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("{user}"), "{}", stderr);
+}
+
+#[test]
+fn roster_mistakes_are_named() {
+    let dir = scratch_dir("roster_mistakes");
+    let roster = dir.join("roster.toml");
+    let file = dir.join("{user}.xml");
+    let export = |contents: &str| {
+        fs::write(&roster, contents).unwrap();
+        let output = run_export(
+            "http://127.0.0.1:9",
+            &[
+                "--roster",
+                roster.to_str().unwrap(),
+                "-f",
+                file.to_str().unwrap(),
+            ],
+        );
+        assert_eq!(output.status.code(), Some(10), "{:?}", output);
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let stderr = export("users = [\"alice\", { user = \"bob\", nsfv = false }]\n");
+    assert!(stderr.contains("unknown field `nsfv`"), "{}", stderr);
+    let stderr = export("users = [{ user = \"bob\", list = \"anim\" }]\n");
+    assert!(stderr.contains("anim"), "{}", stderr);
+    let stderr = export("users = [42]\n");
+    assert!(
+        stderr.contains("a username or a table with a user"),
+        "{}",
+        stderr
+    );
+}